@@ -0,0 +1,196 @@
+// Copyright 2024 Vladimir Kharchev
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The `batch` subcommand, which runs many simulations described by a spec file.
+//!
+//! The spec file is a TOML or JSON document with an array `runs`. Every run is a table whose keys
+//! are the names of the long options of a single simulation, for example
+//!
+//! ```toml
+//! [[runs]]
+//! name = "lilliefors-20"
+//! test = "lilliefors"
+//! samples = 20
+//! test-statistic = 0.2
+//! seed = 1
+//! ```
+//!
+//! The keys `test` and `samples` are the positional arguments, `name` labels the result, a `true`
+//! value is a flag, `false` omits the option, and an array repeats the option for every element.
+
+use std::fs;
+use std::io::{self, Write};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+
+use clap::{error::ErrorKind, Args, CommandFactory, Parser};
+use serde_json::{json, Map, Value};
+
+use crate::error::CliError;
+use crate::output;
+use crate::simulation::parallel_map;
+use crate::{run_simulation, Cli};
+
+/// The key of a run that labels its result.
+const NAME: &str = "name";
+/// The keys of a run that are the positional arguments, in their order on the command line.
+const POSITIONAL: [&str; 2] = ["samples", "test"];
+
+/// The command-line arguments of the `batch` subcommand.
+#[derive(Args, Debug)]
+pub struct BatchArg {
+    /// The spec file describing the runs, in TOML if its extension is `.toml` and in JSON
+    /// otherwise.
+    #[arg(long, value_name = "FILE")]
+    spec: PathBuf,
+    /// Number of runs executed at the same time.
+    ///
+    /// Every run also uses the number of threads given by its own `threads` key.
+    #[arg(long, default_value = "1")]
+    jobs: NonZeroUsize,
+    /// The file to which the combined results are written, instead of the standard output.
+    #[arg(long, value_name = "FILE")]
+    output: Option<PathBuf>,
+}
+
+/// Reads the runs from the spec file.
+fn read_spec(path: &Path) -> Result<Vec<Map<String, Value>>, String> {
+    let text =
+        fs::read_to_string(path).map_err(|err| format!("can't read {}: {err}", path.display()))?;
+    let invalid = |err: String| format!("{} is not a valid spec: {err}", path.display());
+    let spec: Value = if path
+        .extension()
+        .is_some_and(|extension| extension == "toml")
+    {
+        let spec: toml::Value = toml::from_str(&text).map_err(|err| invalid(err.to_string()))?;
+        serde_json::to_value(spec).map_err(|err| invalid(err.to_string()))?
+    } else {
+        serde_json::from_str(&text).map_err(|err| invalid(err.to_string()))?
+    };
+    let Some(Value::Array(runs)) = spec.get("runs") else {
+        return Err(invalid("expected an array `runs`".to_owned()));
+    };
+    runs.iter()
+        .enumerate()
+        .map(|(index, run)| match run {
+            Value::Object(run) => Ok(run.clone()),
+            _ => Err(invalid(format!("run {} is not a table", index + 1))),
+        })
+        .collect()
+}
+
+/// Converts a value of the spec to a command-line argument.
+fn argument(key: &str, value: &Value) -> Result<String, String> {
+    match value {
+        Value::String(value) => Ok(value.clone()),
+        Value::Number(value) => Ok(value.to_string()),
+        _ => Err(format!("invalid value of `{key}`: {value}")),
+    }
+}
+
+/// Converts a run of the spec to the command-line arguments of a single simulation.
+fn arguments(run: &Map<String, Value>) -> Result<Vec<String>, String> {
+    let mut args = vec![Cli::command().get_name().to_owned()];
+    for (key, value) in run {
+        if key == NAME || POSITIONAL.contains(&key.as_str()) {
+            continue;
+        }
+        let option = format!("--{key}");
+        match value {
+            Value::Bool(true) => args.push(option),
+            Value::Bool(false) => {}
+            Value::Array(values) => {
+                for value in values {
+                    args.push(option.clone());
+                    args.push(argument(key, value)?);
+                }
+            }
+            value => {
+                args.push(option);
+                args.push(argument(key, value)?);
+            }
+        }
+    }
+    for key in POSITIONAL {
+        if let Some(value) = run.get(key) {
+            args.push(argument(key, value)?);
+        }
+    }
+    Ok(args)
+}
+
+/// Runs a single simulation of the spec and returns its result in JSON.
+fn run_one(run: &Map<String, Value>) -> Result<Value, CliError> {
+    let args = arguments(run).map_err(CliError::Parameter)?;
+    let cli = Cli::try_parse_from(args)?;
+    if cli.command.is_some() {
+        return Err(clap::Error::raw(
+            ErrorKind::InvalidSubcommand,
+            "a run can't be a subcommand\n",
+        )
+        .into());
+    }
+    let (metadata, outcome) = run_simulation(cli)?;
+    Ok(output::to_json(&metadata, &outcome))
+}
+
+/// Runs the simulations described by the spec file in `args` and writes their combined results.
+///
+/// The results are written as a JSON array in the order of the runs in the spec. A run that fails
+/// doesn't stop the others; its result contains the error message, and the command fails after all
+/// the results are written with the kind of error of the first failed run.
+pub fn run(args: &BatchArg) -> Result<(), CliError> {
+    let runs = read_spec(&args.spec).map_err(CliError::Io)?;
+    let results = parallel_map(0..runs.len(), args.jobs, |index| run_one(&runs[index]));
+    let mut errors = Vec::new();
+    let results: Vec<Value> = results
+        .into_iter()
+        .enumerate()
+        .map(|(index, result)| {
+            let mut result = match result {
+                Ok(Value::Object(result)) => result,
+                Ok(_) => unreachable!("the result of a simulation is an object"),
+                Err(err) => {
+                    let mut result = Map::new();
+                    result.insert("error".to_owned(), json!(err.to_string()));
+                    errors.push(err);
+                    result
+                }
+            };
+            if let Some(name) = runs[index].get(NAME) {
+                result.insert(NAME.to_owned(), name.clone());
+            }
+            Value::Object(result)
+        })
+        .collect();
+
+    write(args.output.as_deref(), &Value::Array(results))?;
+    match errors.first() {
+        Some(err) => {
+            Err(err.with_message(format!("{} of {} runs failed", errors.len(), runs.len())))
+        }
+        None => Ok(()),
+    }
+}
+
+/// Writes `results` to the file at `path`, or to the standard output if there is no path.
+fn write(path: Option<&Path>, results: &Value) -> io::Result<()> {
+    let mut out: Box<dyn Write> = match path {
+        Some(path) => Box::new(io::BufWriter::new(fs::File::create(path)?)),
+        None => Box::new(io::stdout().lock()),
+    };
+    serde_json::to_writer_pretty(&mut out, results)?;
+    writeln!(out)?;
+    out.flush()
+}
@@ -0,0 +1,212 @@
+// Copyright 2024 Vladimir Kharchev
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The on-disk cache of simulated distributions.
+//!
+//! Every entry is a distribution saved in the format of [`crate::store`]. The name of the entry is
+//! made of the test, the null distribution with its parameters, the fitted family, the sample
+//! size, the number of iterations and the seed, which determine the simulated statistics.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use clap::{error::ErrorKind, Args, Subcommand};
+
+use crate::error::CliError;
+use crate::output::Metadata;
+use crate::store::{self, Saved, StoreError};
+
+/// The extension of the files of the entries.
+const EXTENSION: &str = "mcd";
+
+/// The extension of the temporary files to which the entries are written before they are renamed.
+const TEMPORARY_EXTENSION: &str = "tmp";
+
+/// The largest number of iterations of a cached simulation.
+///
+/// The statistics of a cached simulation are kept in memory to be stored, instead of only being
+/// compared with the tested statistic, and every statistic takes 8 bytes in the entry, so larger
+/// simulations are not cached.
+pub const MAX_ITERATIONS: usize = 10_000_000;
+
+/// The number of temporary files created by the process, which makes their names unique.
+static TEMPORARY_FILES: AtomicUsize = AtomicUsize::new(0);
+
+/// The command-line argument choosing the directory of the cache.
+#[derive(Args, Clone, Debug)]
+pub struct CacheDirArg {
+    /// The directory of the cache of simulated distributions.
+    ///
+    /// The default is `monty_carlos_cli` in `$XDG_CACHE_HOME`, or in `$HOME/.cache` if it is not
+    /// set.
+    #[arg(long, value_name = "DIR", env = "MONTY_CARLOS_CACHE_DIR")]
+    cache_dir: Option<PathBuf>,
+}
+
+impl CacheDirArg {
+    /// The directory of the cache.
+    ///
+    /// Returns `None` if no directory is given and the default one is unknown.
+    pub fn dir(&self) -> Option<PathBuf> {
+        self.cache_dir.clone().or_else(|| {
+            let base = env::var_os("XDG_CACHE_HOME")
+                .filter(|base| !base.is_empty())
+                .map(PathBuf::from)
+                .or_else(|| Some(PathBuf::from(env::var_os("HOME")?).join(".cache")))?;
+            Some(base.join("monty_carlos_cli"))
+        })
+    }
+}
+
+/// The command-line arguments controlling the cache of a simulation.
+///
+/// A simulation is cached only if its seed is given, because a random seed never repeats.
+#[derive(Args, Clone, Debug)]
+pub struct CacheArg {
+    /// The directory of the cache.
+    #[command(flatten)]
+    pub dir: CacheDirArg,
+    /// Neither look up the simulated distribution in the cache nor store it there.
+    #[arg(long)]
+    pub no_cache: bool,
+}
+
+impl CacheArg {
+    /// The file of the entry of the simulation described by `metadata`.
+    ///
+    /// Returns `None` if the cache is disabled or the simulation has more than
+    /// [`MAX_ITERATIONS`] iterations.
+    pub fn entry(&self, metadata: &Metadata) -> Option<PathBuf> {
+        if self.no_cache || metadata.iterations > MAX_ITERATIONS {
+            return None;
+        }
+        Some(
+            self.dir
+                .dir()?
+                .join(format!("{}.{EXTENSION}", key(metadata))),
+        )
+    }
+}
+
+/// The name of the entry of the simulation described by `metadata`.
+fn key(metadata: &Metadata) -> String {
+    let mut key = vec![metadata.test.to_owned(), metadata.distribution.to_owned()];
+    key.extend(
+        metadata
+            .parameters
+            .iter()
+            .map(|(name, value)| format!("{name}={value}")),
+    );
+    key.extend(metadata.fit.map(|fit| format!("fit={fit}")));
+    key.push(format!("n={}", metadata.samples));
+    key.push(format!("iterations={}", metadata.iterations));
+    key.push(format!("seed={}", metadata.seed));
+    key.join("_")
+}
+
+/// The subcommands managing the cache.
+#[derive(Subcommand, Debug)]
+pub enum CacheAction {
+    /// List the cached distributions with the sizes of their files.
+    List(CacheDirArg),
+    /// Remove all the cached distributions.
+    Clear(CacheDirArg),
+}
+
+/// The files with the `extension` in the cache directory, sorted by name.
+///
+/// A missing directory is an empty cache.
+fn files(dir: &Path, extension: &str) -> io::Result<Vec<PathBuf>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err),
+    };
+    let mut paths = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_some_and(|found| found == extension) {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// Runs the subcommand managing the cache.
+pub fn run(action: &CacheAction) -> Result<(), CliError> {
+    let (CacheAction::List(dir) | CacheAction::Clear(dir)) = action;
+    let dir = dir.dir().ok_or_else(|| {
+        clap::Error::raw(
+            ErrorKind::MissingRequiredArgument,
+            "the cache directory is unknown, use --cache-dir\n",
+        )
+    })?;
+    let io_error = |err: io::Error| CliError::Io(format!("{}: {err}", dir.display()));
+    let entries = files(&dir, EXTENSION).map_err(io_error)?;
+    match action {
+        CacheAction::List(_) => {
+            for path in &entries {
+                let size = fs::metadata(path).map_err(io_error)?.len();
+                let name = path.file_stem().unwrap_or_default().to_string_lossy();
+                println!("{name}\t{size}");
+            }
+        }
+        CacheAction::Clear(_) => {
+            // The temporary files are left behind by interrupted insertions
+            let temporary = files(&dir, TEMPORARY_EXTENSION).map_err(io_error)?;
+            for path in entries.iter().chain(&temporary) {
+                fs::remove_file(path).map_err(io_error)?;
+            }
+            println!("removed {} cached distributions", entries.len());
+        }
+    }
+    Ok(())
+}
+
+/// Loads the distribution stored in the cache `entry`.
+///
+/// Returns `None` if there is no such entry or it can't be loaded, in which case the distribution
+/// is simulated again and the entry is replaced.
+pub fn lookup(entry: &Path) -> Option<Saved> {
+    store::load(entry).ok()
+}
+
+/// Stores the `statistics` simulated as described by `metadata` in the cache `entry`.
+///
+/// The entry is written to a temporary file in the cache directory and then renamed, so that a
+/// concurrent lookup never reads a partially written entry, and concurrent insertions of the same
+/// entry don't mix their contents.
+pub fn insert(entry: &Path, metadata: &Metadata, statistics: &[f64]) -> Result<(), StoreError> {
+    if let Some(dir) = entry.parent() {
+        fs::create_dir_all(dir).map_err(|err| StoreError::Io(dir.to_owned(), err))?;
+    }
+    let temporary = entry.with_extension(format!(
+        "{}.{}.{TEMPORARY_EXTENSION}",
+        process::id(),
+        TEMPORARY_FILES.fetch_add(1, Ordering::Relaxed)
+    ));
+    let result = store::save(&temporary, metadata, statistics).and_then(|()| {
+        fs::rename(&temporary, entry).map_err(|err| StoreError::Io(entry.to_owned(), err))
+    });
+    if result.is_err() {
+        // The temporary file may not exist, and the original error is the one worth reporting
+        let _ = fs::remove_file(&temporary);
+    }
+    result
+}
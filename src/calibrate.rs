@@ -0,0 +1,268 @@
+// Copyright 2024 Vladimir Kharchev
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The `calibrate` subcommand, which checks that the simulated p-values of a test are calibrated.
+//!
+//! A reference distribution of the statistic is simulated under the null hypothesis once. Then
+//! more datasets are drawn from the null distribution, and the statistic and the p-value of every
+//! one of them are calculated like for a dataset given by `--data`: the p-value is the upper tail
+//! in the reference distribution, estimated as `(k + 1) / (n + 1)`. If the simulation and the
+//! calculation of the statistic agree, the p-values are uniformly distributed, so the
+//! Kolmogorov-Smirnov test of uniformity shouldn't reject, and the proportion of the p-values at
+//! most `alpha` should be close to `alpha`.
+
+use std::io::{self, Write};
+
+use clap::Args;
+use rand::distributions::Distribution;
+use rand::SeedableRng;
+use rand_chacha::ChaCha12Rng;
+use serde_json::{json, Map, Value};
+use statrs::distribution::Uniform;
+
+use crate::error::CliError;
+use crate::estimate::{self, Counts, Estimate, EstimateArg, IntervalMethod, Tail};
+use crate::fitting::FitFamily;
+use crate::gof::{self, ModelArg, Setup, Test};
+use crate::output::{self, Format};
+use crate::simulation::{RunArg, Simulation};
+use crate::statistic;
+
+/// The command-line arguments of the `calibrate` subcommand.
+#[derive(Args, Debug)]
+pub struct CalibrateArg {
+    /// Number of datasets whose p-values are calculated.
+    #[arg(long, default_value = "1000")]
+    datasets: usize,
+    /// Number of iterations of the simulation of the reference distribution.
+    ///
+    /// It should be much larger than the number of datasets, because the p-values can only be
+    /// multiples of `1 / (iterations + 1)`.
+    #[arg(long)]
+    iterations: Option<usize>,
+    /// The nominal significance levels at which the rejection rates are reported.
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_values_t = [0.1, 0.05, 0.01],
+        value_parser = |value: &str| estimate::parse_probability(value, "the significance level")
+    )]
+    alpha: Vec<f64>,
+    /// The confidence level of the intervals for the rejection rates.
+    #[arg(
+        long,
+        default_value = "0.95",
+        value_parser = |value: &str| estimate::parse_probability(value, "the confidence level")
+    )]
+    confidence: f64,
+    /// The method of the confidence intervals for the rejection rates.
+    #[arg(long, value_enum, default_value_t = IntervalMethod::Wilson)]
+    interval: IntervalMethod,
+    /// The seed, the number of threads and the progress of the simulations.
+    #[command(flatten)]
+    run: RunArg,
+    /// The format of the output.
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+    /// The null hypothesis of the test.
+    #[command(flatten, next_help_heading = "Null distribution")]
+    model: ModelArg,
+    /// Size of the datasets.
+    samples: usize,
+    /// The goodness-of-fit test whose p-values are checked.
+    #[arg(value_enum)]
+    test: Test,
+}
+
+/// The result of the calibration.
+struct Calibration {
+    /// The test and its null hypothesis.
+    setup: Setup,
+    /// The number of iterations of the reference distribution.
+    iterations: usize,
+    /// The seed of the reference distribution.
+    seed: u64,
+    /// The Kolmogorov-Smirnov statistic of the p-values against the uniform distribution.
+    uniformity: f64,
+    /// The asymptotic p-value of the uniformity test.
+    uniformity_pvalue: f64,
+    /// The rejection rate at every nominal significance level.
+    rejections: Vec<(f64, Estimate)>,
+}
+
+/// Simulates the p-values of the datasets described by `args` and checks their distribution.
+///
+/// The datasets are drawn from the null distribution with the seed following the one of the
+/// reference distribution, so that they are independent of it. Their statistics and p-values are
+/// calculated like the ones of a dataset given by `--data` with `--plus-one`.
+fn calibrate(args: &CalibrateArg) -> Result<Calibration, CliError> {
+    let setup = args.model.setup(args.test)?;
+    let seed = args.run.seed();
+    let reference = Simulation::new(
+        setup.sampler(args.samples)?,
+        seed,
+        args.iterations,
+        &args.run,
+    );
+    gof::check_iterations(reference.iterations)?;
+    if args.datasets == 0 {
+        return Err(CliError::Parameter(
+            "the number of datasets must be positive".to_owned(),
+        ));
+    }
+    let distribution = reference.simulate_distribution();
+    let estimate = EstimateArg {
+        plus_one: true,
+        confidence: args.confidence,
+        interval: args.interval,
+    };
+    let mut rng = ChaCha12Rng::seed_from_u64(seed.wrapping_add(1));
+    let mut pvalues = Vec::with_capacity(args.datasets);
+    for _ in 0..args.datasets {
+        let mut data: Vec<f64> = setup
+            .null
+            .sample_iter(&mut rng)
+            .take(args.samples)
+            .collect();
+        data.sort_unstable_by(f64::total_cmp);
+        let statistic = setup.statistic(&data).ok_or_else(|| {
+            let family = setup.fit.map_or("null", FitFamily::name);
+            CliError::Numerical(format!(
+                "the {family} family can't be fitted to a simulated dataset"
+            ))
+        })?;
+        let counts = Counts::new(&distribution, statistic);
+        pvalues.push(estimate.estimate(Tail::Upper, &counts).probability);
+    }
+    pvalues.sort_unstable_by(f64::total_cmp);
+    let uniform = Uniform::new(0.0, 1.0).expect("the standard uniform distribution is valid");
+    let uniformity = statistic::kolmogorov_smirnov(&pvalues, &uniform);
+    let rejections = args
+        .alpha
+        .iter()
+        .map(|&alpha| {
+            let count = pvalues.partition_point(|&pvalue| pvalue <= alpha);
+            let rate = Estimate::new(count, pvalues.len(), args.confidence, args.interval);
+            (alpha, rate)
+        })
+        .collect();
+    Ok(Calibration {
+        setup,
+        iterations: reference.iterations,
+        seed,
+        uniformity,
+        uniformity_pvalue: statistic::kolmogorov_smirnov_pvalue(uniformity, pvalues.len()),
+        rejections,
+    })
+}
+
+/// Checks the calibration described by `args` and prints the result.
+pub fn run(args: &CalibrateArg) -> Result<(), CliError> {
+    let calibration = calibrate(args)?;
+    Ok(print(args, &calibration)?)
+}
+
+/// Prints the `calibration` described by `args` to the standard output.
+fn print(args: &CalibrateArg, calibration: &Calibration) -> io::Result<()> {
+    let mut out = io::stdout().lock();
+    match args.format {
+        Format::Text => {
+            writeln!(
+                out,
+                "uniformity (kolmogorov-smirnov) = {}, pvalue = {}",
+                calibration.uniformity, calibration.uniformity_pvalue
+            )?;
+            for (alpha, rate) in &calibration.rejections {
+                writeln!(
+                    out,
+                    "rejection rate at {alpha} = {} [{}, {}]",
+                    rate.probability, rate.lower, rate.upper
+                )?;
+            }
+            Ok(())
+        }
+        Format::Json => {
+            let null = &calibration.setup.distribution;
+            let rejections = calibration
+                .rejections
+                .iter()
+                .map(|(alpha, rate)| {
+                    let mut rejection = Map::new();
+                    rejection.insert("alpha".to_owned(), json!(alpha));
+                    output::insert_estimate(&mut rejection, "rejections", "rate", rate);
+                    Value::Object(rejection)
+                })
+                .collect();
+            let mut object = Map::new();
+            object.insert("test".to_owned(), json!(args.test.name()));
+            object.insert("samples".to_owned(), json!(args.samples));
+            object.insert(
+                "distribution".to_owned(),
+                output::distribution_json(null.family().name(), &null.parameters()),
+            );
+            object.insert(
+                "fit".to_owned(),
+                json!(calibration.setup.fit.map(FitFamily::name)),
+            );
+            object.insert("iterations".to_owned(), json!(calibration.iterations));
+            object.insert("datasets".to_owned(), json!(args.datasets));
+            object.insert("seed".to_owned(), json!(calibration.seed));
+            object.insert(
+                "uniformity".to_owned(),
+                json!({
+                    "test": Test::KolmogorovSmirnov.name(),
+                    "statistic": calibration.uniformity,
+                    "pvalue": calibration.uniformity_pvalue,
+                }),
+            );
+            object.insert("rejections".to_owned(), Value::Array(rejections));
+            serde_json::to_writer_pretty(&mut out, &Value::Object(object))?;
+            writeln!(out)
+        }
+        Format::Csv | Format::Tsv => {
+            let separator = args.format.separator().unwrap_or_default();
+            writeln!(out, "name{separator}value")?;
+            writeln!(out, "uniformity{separator}{}", calibration.uniformity)?;
+            writeln!(
+                out,
+                "uniformity_pvalue{separator}{}",
+                calibration.uniformity_pvalue
+            )?;
+            writeln!(out)?;
+            let header = [
+                "alpha",
+                "rejections",
+                "rate",
+                "standard_error",
+                "confidence",
+                "lower",
+                "upper",
+            ];
+            writeln!(out, "{}", header.join(separator))?;
+            for (alpha, rate) in &calibration.rejections {
+                let row = [
+                    rate.probability,
+                    rate.standard_error,
+                    rate.confidence,
+                    rate.lower,
+                    rate.upper,
+                ]
+                .map(|value| value.to_string())
+                .join(separator);
+                writeln!(out, "{alpha}{separator}{}{separator}{row}", rate.count)?;
+            }
+            Ok(())
+        }
+    }
+}
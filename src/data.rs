@@ -0,0 +1,160 @@
+// Copyright 2024 Vladimir Kharchev
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Reading of the user's datasets.
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+/// An error that occurred while reading a dataset.
+#[derive(Debug)]
+pub enum DataError {
+    /// The file couldn't be read.
+    Io(PathBuf, io::Error),
+    /// A value in the file is not a finite number.
+    Parse {
+        path: PathBuf,
+        line: usize,
+        token: String,
+    },
+    /// The file contains no values.
+    Empty(PathBuf),
+    /// A row of a file of labelled values doesn't consist of a label and a value.
+    Row { path: PathBuf, line: usize },
+    /// A file of labelled values doesn't have exactly two distinct labels.
+    Groups(PathBuf, usize),
+}
+
+impl fmt::Display for DataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DataError::Io(path, err) => write!(f, "can't read {}: {err}", path.display()),
+            DataError::Parse { path, line, token } => write!(
+                f,
+                "{}:{line}: `{token}` is not a finite number",
+                path.display()
+            ),
+            DataError::Empty(path) => write!(f, "{} contains no values", path.display()),
+            DataError::Row { path, line } => write!(
+                f,
+                "{}:{line}: expected a label and a value separated by a comma",
+                path.display()
+            ),
+            DataError::Groups(path, count) => write!(
+                f,
+                "{} must contain exactly two groups, found {count}",
+                path.display()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DataError {}
+
+/// Opens the file at `path`, or the standard input if `path` is `-`.
+fn open(path: &Path) -> Result<Box<dyn BufRead>, DataError> {
+    if path == Path::new("-") {
+        Ok(Box::new(io::stdin().lock()))
+    } else {
+        let file = File::open(path).map_err(|err| DataError::Io(path.to_owned(), err))?;
+        Ok(Box::new(BufReader::new(file)))
+    }
+}
+
+/// Reads a dataset from the file at `path`, or from the standard input if `path` is `-`.
+///
+/// The values are separated by whitespace or commas. Empty lines and everything after `#` on a
+/// line are ignored.
+pub fn read(path: &Path) -> Result<Vec<f64>, DataError> {
+    let io_error = |err: io::Error| DataError::Io(path.to_owned(), err);
+    let reader = open(path)?;
+    let mut data = Vec::new();
+    for (index, line) in reader.lines().enumerate() {
+        let line = line.map_err(io_error)?;
+        let content = line.split('#').next().unwrap_or_default();
+        for token in content
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|token| !token.is_empty())
+        {
+            match token.parse::<f64>() {
+                Ok(value) if value.is_finite() => data.push(value),
+                _ => {
+                    return Err(DataError::Parse {
+                        path: path.to_owned(),
+                        line: index + 1,
+                        token: token.to_owned(),
+                    })
+                }
+            }
+        }
+    }
+    if data.is_empty() {
+        return Err(DataError::Empty(path.to_owned()));
+    }
+    Ok(data)
+}
+
+/// Reads two groups of labelled values from a CSV file at `path`, or from the standard input if
+/// `path` is `-`.
+///
+/// Every row consists of a label and a value separated by a comma. The groups are returned in the
+/// order in which their labels first appear. A first row whose value is not a number is a header
+/// and is skipped. Empty lines and everything after `#` on a line are ignored.
+pub fn read_groups(path: &Path) -> Result<[(String, Vec<f64>); 2], DataError> {
+    let io_error = |err: io::Error| DataError::Io(path.to_owned(), err);
+    let reader = open(path)?;
+    let mut groups: Vec<(String, Vec<f64>)> = Vec::new();
+    let mut first = true;
+    for (index, line) in reader.lines().enumerate() {
+        let line = line.map_err(io_error)?;
+        let content = line.split('#').next().unwrap_or_default().trim();
+        if content.is_empty() {
+            continue;
+        }
+        let Some((label, token)) = content.split_once(',') else {
+            return Err(DataError::Row {
+                path: path.to_owned(),
+                line: index + 1,
+            });
+        };
+        let (label, token) = (label.trim(), token.trim());
+        match token.parse::<f64>() {
+            Ok(value) if value.is_finite() => {
+                match groups.iter_mut().find(|(name, _)| name == label) {
+                    Some((_, values)) => values.push(value),
+                    None => groups.push((label.to_owned(), vec![value])),
+                }
+            }
+            Err(_) if first => {}
+            _ => {
+                return Err(DataError::Parse {
+                    path: path.to_owned(),
+                    line: index + 1,
+                    token: token.to_owned(),
+                })
+            }
+        }
+        first = false;
+    }
+    let count = groups.len();
+    <[_; 2]>::try_from(groups).map_err(|_| {
+        if count == 0 {
+            DataError::Empty(path.to_owned())
+        } else {
+            DataError::Groups(path.to_owned(), count)
+        }
+    })
+}
@@ -0,0 +1,432 @@
+// Copyright 2024 Vladimir Kharchev
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The null distributions that can be chosen from the command line.
+
+use clap::{Args, ValueEnum};
+use rand::distributions::Distribution;
+use rand::Rng;
+use statrs::distribution::{
+    Beta, ChiSquared, ContinuousCDF, Exp, Gamma, LogNormal, Normal, StudentsT, Uniform, Weibull,
+};
+use statrs::statistics::{Max, Min};
+use statrs::StatsError;
+
+/// Enum for the CLI option to choose the family of the null distribution.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Family {
+    /// Normal distribution with `--mean` and `--std-dev`.
+    Normal,
+    /// Exponential distribution with `--rate`.
+    Exponential,
+    /// Continuous uniform distribution with `--min` and `--max`.
+    Uniform,
+    /// Gamma distribution with `--shape` and `--rate`.
+    Gamma,
+    /// Weibull distribution with `--shape` and `--scale`.
+    Weibull,
+    /// Log-normal distribution with `--location` and `--scale`.
+    LogNormal,
+    /// Beta distribution with `--shape-a` and `--shape-b`.
+    Beta,
+    /// Student's t-distribution with `--location`, `--scale` and `--freedom`.
+    StudentsT,
+    /// Chi-squared distribution with `--freedom`.
+    ChiSquared,
+}
+
+/// A parameter of a distribution, as it is named on the command line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Parameter {
+    Mean,
+    StdDev,
+    Rate,
+    Shape,
+    Scale,
+    Min,
+    Max,
+    Location,
+    ShapeA,
+    ShapeB,
+    Freedom,
+}
+
+impl Parameter {
+    /// All the parameters.
+    const ALL: [Parameter; 11] = [
+        Parameter::Mean,
+        Parameter::StdDev,
+        Parameter::Rate,
+        Parameter::Shape,
+        Parameter::Scale,
+        Parameter::Min,
+        Parameter::Max,
+        Parameter::Location,
+        Parameter::ShapeA,
+        Parameter::ShapeB,
+        Parameter::Freedom,
+    ];
+
+    /// The name of the parameter, which is also the name of its command-line option.
+    fn name(self) -> &'static str {
+        match self {
+            Parameter::Mean => "mean",
+            Parameter::StdDev => "std-dev",
+            Parameter::Rate => "rate",
+            Parameter::Shape => "shape",
+            Parameter::Scale => "scale",
+            Parameter::Min => "min",
+            Parameter::Max => "max",
+            Parameter::Location => "location",
+            Parameter::ShapeA => "shape-a",
+            Parameter::ShapeB => "shape-b",
+            Parameter::Freedom => "freedom",
+        }
+    }
+
+    /// The value of the parameter in the standard member of the family.
+    ///
+    /// Returns `None` if the parameter has to be given explicitly.
+    fn default(self) -> Option<f64> {
+        match self {
+            Parameter::Mean | Parameter::Min | Parameter::Location => Some(0.0),
+            Parameter::StdDev
+            | Parameter::Rate
+            | Parameter::Shape
+            | Parameter::Scale
+            | Parameter::Max
+            | Parameter::ShapeA
+            | Parameter::ShapeB => Some(1.0),
+            Parameter::Freedom => None,
+        }
+    }
+}
+
+impl Family {
+    /// The parameters that are accepted by the family.
+    fn parameters(self) -> &'static [Parameter] {
+        match self {
+            Family::Normal => &[Parameter::Mean, Parameter::StdDev],
+            Family::Exponential => &[Parameter::Rate],
+            Family::Uniform => &[Parameter::Min, Parameter::Max],
+            Family::Gamma => &[Parameter::Shape, Parameter::Rate],
+            Family::Weibull => &[Parameter::Shape, Parameter::Scale],
+            Family::LogNormal => &[Parameter::Location, Parameter::Scale],
+            Family::Beta => &[Parameter::ShapeA, Parameter::ShapeB],
+            Family::StudentsT => &[Parameter::Location, Parameter::Scale, Parameter::Freedom],
+            Family::ChiSquared => &[Parameter::Freedom],
+        }
+    }
+
+    /// The names of the parameters that are accepted by the family.
+    pub fn parameter_names(self) -> impl Iterator<Item = &'static str> {
+        self.parameters().iter().map(|p| p.name())
+    }
+
+    /// The name of the family as it is written on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            Family::Normal => "normal",
+            Family::Exponential => "exponential",
+            Family::Uniform => "uniform",
+            Family::Gamma => "gamma",
+            Family::Weibull => "weibull",
+            Family::LogNormal => "log-normal",
+            Family::Beta => "beta",
+            Family::StudentsT => "students-t",
+            Family::ChiSquared => "chi-squared",
+        }
+    }
+}
+
+/// The command-line arguments describing the null distribution.
+///
+/// Every parameter is optional on the command line. The parameters that are not given take the
+/// value of the standard member of the family, except for the degrees of freedom, which have to be
+/// given explicitly. Giving a parameter that the chosen family doesn't have is an error.
+#[derive(Args, Clone, Copy, Debug)]
+pub struct DistributionArg {
+    /// The family of the null distribution [default: the family chosen by `--fit`, or normal].
+    #[arg(long, value_enum)]
+    pub distribution: Option<Family>,
+    /// The mean of the normal distribution [default: 0].
+    #[arg(long, allow_hyphen_values = true)]
+    pub mean: Option<f64>,
+    /// The standard deviation of the normal distribution [default: 1].
+    #[arg(long, allow_hyphen_values = true)]
+    pub std_dev: Option<f64>,
+    /// The rate of the exponential or gamma distribution [default: 1].
+    #[arg(long, allow_hyphen_values = true)]
+    pub rate: Option<f64>,
+    /// The shape of the gamma or Weibull distribution [default: 1].
+    #[arg(long, allow_hyphen_values = true)]
+    pub shape: Option<f64>,
+    /// The scale of the Weibull, log-normal or Student's t-distribution [default: 1].
+    #[arg(long, allow_hyphen_values = true)]
+    pub scale: Option<f64>,
+    /// The lower bound of the uniform distribution [default: 0].
+    #[arg(long, allow_hyphen_values = true)]
+    pub min: Option<f64>,
+    /// The upper bound of the uniform distribution [default: 1].
+    #[arg(long, allow_hyphen_values = true)]
+    pub max: Option<f64>,
+    /// The location of the log-normal or Student's t-distribution [default: 0].
+    #[arg(long, allow_hyphen_values = true)]
+    pub location: Option<f64>,
+    /// The first shape parameter (alpha) of the beta distribution [default: 1].
+    #[arg(long, allow_hyphen_values = true)]
+    pub shape_a: Option<f64>,
+    /// The second shape parameter (beta) of the beta distribution [default: 1].
+    #[arg(long, allow_hyphen_values = true)]
+    pub shape_b: Option<f64>,
+    /// The degrees of freedom of the Student's t or chi-squared distribution.
+    #[arg(long, allow_hyphen_values = true)]
+    pub freedom: Option<f64>,
+}
+
+impl DistributionArg {
+    /// The family of the distribution, which is normal unless it is given.
+    pub fn family(&self) -> Family {
+        self.distribution.unwrap_or(Family::Normal)
+    }
+
+    /// Checks whether the family or any parameter is given on the command line.
+    pub fn is_given(&self) -> bool {
+        self.distribution.is_some() || Parameter::ALL.into_iter().any(|p| self.get(p).is_some())
+    }
+
+    /// The value of the parameter given on the command line.
+    fn get(&self, parameter: Parameter) -> Option<f64> {
+        match parameter {
+            Parameter::Mean => self.mean,
+            Parameter::StdDev => self.std_dev,
+            Parameter::Rate => self.rate,
+            Parameter::Shape => self.shape,
+            Parameter::Scale => self.scale,
+            Parameter::Min => self.min,
+            Parameter::Max => self.max,
+            Parameter::Location => self.location,
+            Parameter::ShapeA => self.shape_a,
+            Parameter::ShapeB => self.shape_b,
+            Parameter::Freedom => self.freedom,
+        }
+    }
+
+    /// The mutable value of the parameter given on the command line.
+    fn get_mut(&mut self, parameter: Parameter) -> &mut Option<f64> {
+        match parameter {
+            Parameter::Mean => &mut self.mean,
+            Parameter::StdDev => &mut self.std_dev,
+            Parameter::Rate => &mut self.rate,
+            Parameter::Shape => &mut self.shape,
+            Parameter::Scale => &mut self.scale,
+            Parameter::Min => &mut self.min,
+            Parameter::Max => &mut self.max,
+            Parameter::Location => &mut self.location,
+            Parameter::ShapeA => &mut self.shape_a,
+            Parameter::ShapeB => &mut self.shape_b,
+            Parameter::Freedom => &mut self.freedom,
+        }
+    }
+
+    /// Parses a distribution written as `FAMILY[:PARAMETER=VALUE,...]`, for example
+    /// `students-t:freedom=5`.
+    ///
+    /// The parameters are named like their command-line options without the leading dashes. The
+    /// parameters are checked only when the distribution is built.
+    pub fn from_spec(spec: &str) -> Result<Self, String> {
+        let (family, parameters) = spec.split_once(':').unwrap_or((spec, ""));
+        let mut arg = Self {
+            distribution: Some(Family::from_str(family, false)?),
+            mean: None,
+            std_dev: None,
+            rate: None,
+            shape: None,
+            scale: None,
+            min: None,
+            max: None,
+            location: None,
+            shape_a: None,
+            shape_b: None,
+            freedom: None,
+        };
+        for parameter in parameters
+            .split(',')
+            .filter(|parameter| !parameter.is_empty())
+        {
+            let (name, value) = parameter
+                .split_once('=')
+                .ok_or_else(|| format!("expected PARAMETER=VALUE, got `{parameter}`"))?;
+            let value = value
+                .parse()
+                .map_err(|err| format!("invalid value of {name}: {err}"))?;
+            arg.set(name, value)?;
+        }
+        Ok(arg)
+    }
+
+    /// Sets the parameter called `name` like its command-line option to `value`.
+    pub fn set(&mut self, name: &str, value: f64) -> Result<(), String> {
+        let parameter = Parameter::ALL
+            .into_iter()
+            .find(|parameter| parameter.name() == name)
+            .ok_or_else(|| format!("unknown parameter `{name}`"))?;
+        *self.get_mut(parameter) = Some(value);
+        Ok(())
+    }
+
+    /// The value of the parameter, either given on the command line or the default one.
+    fn value(&self, parameter: Parameter) -> Result<f64, String> {
+        self.get(parameter).or(parameter.default()).ok_or_else(|| {
+            format!(
+                "the {} distribution requires --{}",
+                self.family().name(),
+                parameter.name()
+            )
+        })
+    }
+
+    /// The names and the values of the parameters of the family.
+    ///
+    /// The parameters that are not given on the command line have their default values. A
+    /// parameter without a default value that is not given is omitted.
+    pub fn parameters(&self) -> Vec<(&'static str, f64)> {
+        self.family()
+            .parameters()
+            .iter()
+            .filter_map(|&p| Some((p.name(), self.value(p).ok()?)))
+            .collect()
+    }
+
+    /// Constructs the null distribution described by the arguments.
+    ///
+    /// Returns an error message if a parameter is given that doesn't belong to the family, if a
+    /// required parameter is missing, or if the parameters are rejected by [`statrs`].
+    pub fn build(&self) -> Result<Null, String> {
+        let family = self.family();
+        if let Some(extra) = Parameter::ALL
+            .into_iter()
+            .find(|&p| self.get(p).is_some() && !family.parameters().contains(&p))
+        {
+            return Err(format!(
+                "--{} is not a parameter of the {} distribution",
+                extra.name(),
+                family.name()
+            ));
+        }
+        let invalid = |err: StatsError| {
+            format!(
+                "invalid parameters of the {} distribution: {err}",
+                family.name()
+            )
+        };
+        let value = |p: Parameter| self.value(p);
+        let null = match family {
+            Family::Normal => Null::Normal(
+                Normal::new(value(Parameter::Mean)?, value(Parameter::StdDev)?).map_err(invalid)?,
+            ),
+            Family::Exponential => {
+                Null::Exponential(Exp::new(value(Parameter::Rate)?).map_err(invalid)?)
+            }
+            Family::Uniform => Null::Uniform(
+                Uniform::new(value(Parameter::Min)?, value(Parameter::Max)?).map_err(invalid)?,
+            ),
+            Family::Gamma => Null::Gamma(
+                Gamma::new(value(Parameter::Shape)?, value(Parameter::Rate)?).map_err(invalid)?,
+            ),
+            Family::Weibull => Null::Weibull(
+                Weibull::new(value(Parameter::Shape)?, value(Parameter::Scale)?)
+                    .map_err(invalid)?,
+            ),
+            Family::LogNormal => Null::LogNormal(
+                LogNormal::new(value(Parameter::Location)?, value(Parameter::Scale)?)
+                    .map_err(invalid)?,
+            ),
+            Family::Beta => Null::Beta(
+                Beta::new(value(Parameter::ShapeA)?, value(Parameter::ShapeB)?).map_err(invalid)?,
+            ),
+            Family::StudentsT => Null::StudentsT(
+                StudentsT::new(
+                    value(Parameter::Location)?,
+                    value(Parameter::Scale)?,
+                    value(Parameter::Freedom)?,
+                )
+                .map_err(invalid)?,
+            ),
+            Family::ChiSquared => {
+                Null::ChiSquared(ChiSquared::new(value(Parameter::Freedom)?).map_err(invalid)?)
+            }
+        };
+        Ok(null)
+    }
+}
+
+/// A continuous distribution from [`statrs`] that can be used as the null distribution.
+#[derive(Clone, Copy, Debug)]
+pub enum Null {
+    Normal(Normal),
+    Exponential(Exp),
+    Uniform(Uniform),
+    Gamma(Gamma),
+    Weibull(Weibull),
+    LogNormal(LogNormal),
+    Beta(Beta),
+    StudentsT(StudentsT),
+    ChiSquared(ChiSquared),
+}
+
+/// Calls the same method on the distribution inside any variant of [`Null`].
+macro_rules! dispatch {
+    ($null:expr, $d:ident => $body:expr) => {
+        match $null {
+            Null::Normal($d) => $body,
+            Null::Exponential($d) => $body,
+            Null::Uniform($d) => $body,
+            Null::Gamma($d) => $body,
+            Null::Weibull($d) => $body,
+            Null::LogNormal($d) => $body,
+            Null::Beta($d) => $body,
+            Null::StudentsT($d) => $body,
+            Null::ChiSquared($d) => $body,
+        }
+    };
+}
+
+impl Distribution<f64> for Null {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> f64 {
+        dispatch!(self, d => d.sample(rng))
+    }
+}
+
+impl Min<f64> for Null {
+    fn min(&self) -> f64 {
+        dispatch!(self, d => d.min())
+    }
+}
+
+impl Max<f64> for Null {
+    fn max(&self) -> f64 {
+        dispatch!(self, d => d.max())
+    }
+}
+
+impl ContinuousCDF<f64, f64> for Null {
+    fn cdf(&self, x: f64) -> f64 {
+        dispatch!(self, d => d.cdf(x))
+    }
+
+    fn sf(&self, x: f64) -> f64 {
+        dispatch!(self, d => d.sf(x))
+    }
+}
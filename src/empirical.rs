@@ -0,0 +1,156 @@
+// Copyright 2024 Vladimir Kharchev
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Properties of the empirical distribution of simulated statistics.
+
+use std::num::NonZeroUsize;
+
+/// Calculates the empirical quantile of order `q` of the `sorted` values.
+///
+/// The quantile is interpolated linearly between the order statistics, which is the definition 7
+/// of Hyndman and Fan (1996) and the default one of R.
+///
+/// # Panics
+///
+/// Panics if `sorted` is empty.
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_precision_loss,
+    clippy::cast_sign_loss
+)]
+pub fn quantile(sorted: &[f64], q: f64) -> f64 {
+    assert!(!sorted.is_empty(), "quantile of an empty distribution");
+    let position = q.clamp(0.0, 1.0) * (sorted.len() - 1) as f64;
+    let lower = position.floor() as usize;
+    let upper = position.ceil() as usize;
+    if lower == upper {
+        return sorted[lower];
+    }
+    // A weighted sum rather than a difference, so that infinite statistics don't produce NaN
+    let fraction = position - position.floor();
+    sorted[lower] * (1.0 - fraction) + sorted[upper] * fraction
+}
+
+/// The orders of the quantiles reported in a [`Summary`].
+pub const SUMMARY_ORDERS: [f64; 7] = [0.01, 0.05, 0.25, 0.5, 0.75, 0.95, 0.99];
+
+/// Descriptive statistics of the simulated statistics.
+#[derive(Clone, Debug)]
+pub struct Summary {
+    /// The mean.
+    pub mean: f64,
+    /// The unbiased estimate of the variance.
+    pub variance: f64,
+    /// The moment coefficient of skewness.
+    pub skewness: f64,
+    /// The moment coefficient of excess kurtosis.
+    pub kurtosis: f64,
+    /// The smallest value.
+    pub min: f64,
+    /// The largest value.
+    pub max: f64,
+    /// The empirical quantiles of the orders [`SUMMARY_ORDERS`].
+    pub quantiles: Vec<(f64, f64)>,
+}
+
+impl Summary {
+    /// Describes the `sorted` values.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sorted` is empty.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn new(sorted: &[f64]) -> Self {
+        let n = sorted.len() as f64;
+        let mean = sorted.iter().sum::<f64>() / n;
+        let moment = |k: i32| sorted.iter().map(|x| (x - mean).powi(k)).sum::<f64>() / n;
+        let (m2, m3, m4) = (moment(2), moment(3), moment(4));
+        Self {
+            mean,
+            variance: m2 * n / (n - 1.0),
+            skewness: m3 / m2.powf(1.5),
+            kurtosis: m4 / (m2 * m2) - 3.0,
+            min: sorted[0],
+            max: sorted[sorted.len() - 1],
+            quantiles: SUMMARY_ORDERS
+                .iter()
+                .map(|&q| (q, quantile(sorted, q)))
+                .collect(),
+        }
+    }
+}
+
+/// A bin of a [`Histogram`].
+#[derive(Clone, Copy, Debug)]
+pub struct Bin {
+    /// The lower bound of the bin.
+    pub lower: f64,
+    /// The upper bound of the bin.
+    pub upper: f64,
+    /// The number of values in the bin.
+    pub count: usize,
+}
+
+/// A histogram of the simulated statistics with bins of equal width.
+#[derive(Clone, Debug)]
+pub struct Histogram {
+    /// The bins from the smallest to the largest finite value.
+    ///
+    /// Every bin contains its lower bound, and the last bin also contains its upper bound.
+    pub bins: Vec<Bin>,
+    /// The number of infinite or NaN values, which are not in any bin.
+    pub non_finite: usize,
+}
+
+impl Histogram {
+    /// Distributes `values` into `bins` bins.
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss
+    )]
+    pub fn new(values: &[f64], bins: NonZeroUsize) -> Self {
+        let bins = bins.get();
+        let finite: Vec<f64> = values.iter().copied().filter(|x| x.is_finite()).collect();
+        let non_finite = values.len() - finite.len();
+        let min = finite.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = finite.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        if finite.is_empty() {
+            return Self {
+                bins: Vec::new(),
+                non_finite,
+            };
+        }
+        let width = (max - min) / bins as f64;
+        let mut counts = vec![0; bins];
+        for x in finite {
+            let index = if width > 0.0 {
+                (((x - min) / width) as usize).min(bins - 1)
+            } else {
+                0
+            };
+            counts[index] += 1;
+        }
+        let bins = counts
+            .into_iter()
+            .enumerate()
+            .map(|(i, count)| Bin {
+                lower: min + i as f64 * width,
+                upper: min + (i + 1) as f64 * width,
+                count,
+            })
+            .collect();
+        Self { bins, non_finite }
+    }
+}
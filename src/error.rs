@@ -0,0 +1,118 @@
+// Copyright 2024 Vladimir Kharchev
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The errors of the program and their exit codes.
+//!
+//! | Exit code | Error                                                     |
+//! |-----------|-----------------------------------------------------------|
+//! | 2         | invalid usage of the command line                         |
+//! | 3         | invalid parameters, such as a sample size of zero         |
+//! | 4         | files that can't be read, written or parsed               |
+//! | 5         | numerical failures, such as a family that can't be fitted |
+
+use std::fmt;
+use std::process;
+
+use clap::CommandFactory;
+
+use crate::data::DataError;
+use crate::store::StoreError;
+use crate::Cli;
+
+/// An error that stops the program.
+#[derive(Debug)]
+pub enum CliError {
+    /// The command line is invalid.
+    Usage(clap::Error),
+    /// The parameters of the simulation are invalid.
+    Parameter(String),
+    /// A file couldn't be read, written or parsed.
+    Io(String),
+    /// A calculation failed.
+    Numerical(String),
+}
+
+impl CliError {
+    /// The exit code of the program that stops with the error.
+    pub fn code(&self) -> i32 {
+        match self {
+            CliError::Usage(err) => err.exit_code(),
+            CliError::Parameter(_) => 3,
+            CliError::Io(_) => 4,
+            CliError::Numerical(_) => 5,
+        }
+    }
+
+    /// The error of the same kind with another `message`.
+    pub fn with_message(&self, message: String) -> Self {
+        match self {
+            CliError::Usage(err) => CliError::Usage(clap::Error::raw(err.kind(), message + "\n")),
+            CliError::Parameter(_) => CliError::Parameter(message),
+            CliError::Io(_) => CliError::Io(message),
+            CliError::Numerical(_) => CliError::Numerical(message),
+        }
+    }
+
+    /// Prints the error to the standard error and exits the program with the code of the error.
+    pub fn exit(self) -> ! {
+        match self {
+            CliError::Usage(err) => err.format(&mut Cli::command()).exit(),
+            err => {
+                eprintln!("error: {err}");
+                process::exit(err.code())
+            }
+        }
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::Usage(err) => {
+                let message = err.render().to_string();
+                let message = message.trim();
+                write!(f, "{}", message.strip_prefix("error: ").unwrap_or(message))
+            }
+            CliError::Parameter(message) | CliError::Io(message) | CliError::Numerical(message) => {
+                write!(f, "{message}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CliError {}
+
+impl From<clap::Error> for CliError {
+    fn from(err: clap::Error) -> Self {
+        CliError::Usage(err)
+    }
+}
+
+impl From<DataError> for CliError {
+    fn from(err: DataError) -> Self {
+        CliError::Io(err.to_string())
+    }
+}
+
+impl From<StoreError> for CliError {
+    fn from(err: StoreError) -> Self {
+        CliError::Io(err.to_string())
+    }
+}
+
+impl From<std::io::Error> for CliError {
+    fn from(err: std::io::Error) -> Self {
+        CliError::Io(err.to_string())
+    }
+}
@@ -0,0 +1,442 @@
+// Copyright 2024 Vladimir Kharchev
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The precision of probabilities estimated by Monte-Carlo simulations.
+//!
+//! A probability estimated from `n` iterations is the proportion `k / n` of the iterations in which
+//! an event happened, so `k` has the binomial distribution and the usual binomial confidence
+//! intervals apply.
+
+use std::cmp::Ordering;
+
+use clap::{Args, ValueEnum};
+use statrs::distribution::{Beta, ContinuousCDF, Normal};
+
+/// Enum for the CLI option to choose the method of the confidence interval.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IntervalMethod {
+    /// Wilson score interval.
+    Wilson,
+    /// Clopper-Pearson exact interval, which is conservative.
+    ClopperPearson,
+}
+
+impl IntervalMethod {
+    /// The name of the method as it is written on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            IntervalMethod::Wilson => "wilson",
+            IntervalMethod::ClopperPearson => "clopper-pearson",
+        }
+    }
+}
+
+/// Enum for the CLI option to choose which tail of the distribution the p-value measures.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tail {
+    /// The probability that the statistic is at most the given value.
+    Lower,
+    /// The probability that the statistic is at least the given value.
+    Upper,
+    /// Twice the smaller of the lower and the upper tail, but at most 1.
+    TwoSided,
+}
+
+impl Tail {
+    /// The name of the tail as it is written on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            Tail::Lower => "lower",
+            Tail::Upper => "upper",
+            Tail::TwoSided => "two-sided",
+        }
+    }
+}
+
+/// The comparison of the simulated statistics with a given value.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Counts {
+    /// The number of statistics less than the value.
+    pub less: usize,
+    /// The number of statistics equal to the value.
+    pub equal: usize,
+    /// The number of statistics greater than the value.
+    pub greater: usize,
+}
+
+impl Counts {
+    /// Compares `statistics` with `value`.
+    ///
+    /// A NaN statistic is counted as greater than any value.
+    pub fn new(statistics: &[f64], value: f64) -> Self {
+        let mut counts = Self::default();
+        for statistic in statistics {
+            match statistic.partial_cmp(&value) {
+                Some(Ordering::Less) => counts.less += 1,
+                Some(Ordering::Equal) => counts.equal += 1,
+                Some(Ordering::Greater) | None => counts.greater += 1,
+            }
+        }
+        counts
+    }
+
+    /// The sum of the counts of two simulations.
+    #[must_use]
+    pub fn add(self, other: Self) -> Self {
+        Self {
+            less: self.less + other.less,
+            equal: self.equal + other.equal,
+            greater: self.greater + other.greater,
+        }
+    }
+
+    /// The number of compared statistics.
+    pub fn iterations(&self) -> usize {
+        self.less + self.equal + self.greater
+    }
+}
+
+/// The command-line arguments describing how a p-value is estimated.
+#[derive(Args, Clone, Copy, Debug)]
+pub struct PValueArg {
+    /// The tail of the distribution of the statistic measured by the p-value.
+    ///
+    /// The statistics equal to the given value belong to both tails. The default is the upper
+    /// tail with `--data`, which is the p-value of the goodness-of-fit test, and the lower tail
+    /// with `--test-statistic`, which is what earlier versions calculated.
+    #[arg(long, value_enum)]
+    pub tail: Option<Tail>,
+    /// How the probability of the tail is estimated.
+    #[command(flatten)]
+    pub estimate: EstimateArg,
+}
+
+impl PValueArg {
+    /// Estimates the p-value in `tail` from the comparison of the simulated statistics with the
+    /// given one.
+    pub fn estimate(&self, tail: Tail, counts: &Counts) -> Estimate {
+        self.estimate.estimate(tail, counts)
+    }
+}
+
+/// The command-line arguments describing how the probability of a tail is estimated.
+#[derive(Args, Clone, Copy, Debug)]
+pub struct EstimateArg {
+    /// Estimate the p-value as `(k + 1) / (n + 1)` instead of `k / n`.
+    ///
+    /// Here `k` is the number of simulated statistics in the tail and `n` is the number of
+    /// iterations. This estimate is never zero, and a test that rejects when it is at most the
+    /// significance level has at most that probability of the type I error.
+    #[arg(long)]
+    pub plus_one: bool,
+    /// The confidence level of the interval for the p-value.
+    #[arg(
+        long,
+        default_value = "0.95",
+        value_parser = |value: &str| parse_probability(value, "the confidence level")
+    )]
+    pub confidence: f64,
+    /// The method of the confidence interval for the p-value.
+    #[arg(long, value_enum, default_value_t = IntervalMethod::Wilson)]
+    pub interval: IntervalMethod,
+}
+
+impl EstimateArg {
+    /// Estimates the probability that the statistic is in `tail` of the given one from the
+    /// comparison of the simulated statistics with it.
+    pub fn estimate(&self, tail: Tail, counts: &Counts) -> Estimate {
+        let estimate = |count: usize| {
+            let (count, iterations) = if self.plus_one {
+                (count + 1, counts.iterations() + 1)
+            } else {
+                (count, counts.iterations())
+            };
+            Estimate::new(count, iterations, self.confidence, self.interval)
+        };
+        let lower = counts.less + counts.equal;
+        let upper = counts.greater + counts.equal;
+        match tail {
+            Tail::Lower => estimate(lower),
+            Tail::Upper => estimate(upper),
+            Tail::TwoSided => estimate(lower.min(upper)).doubled(),
+        }
+    }
+}
+
+/// A probability estimated by a Monte-Carlo simulation.
+#[derive(Clone, Copy, Debug)]
+pub struct Estimate {
+    /// The number of iterations in which the event happened.
+    pub count: usize,
+    /// The number of iterations.
+    pub iterations: usize,
+    /// The estimate `count / iterations` of the probability.
+    pub probability: f64,
+    /// The Monte-Carlo standard error of the estimate.
+    pub standard_error: f64,
+    /// The confidence level of the interval.
+    pub confidence: f64,
+    /// The method of the interval.
+    pub method: IntervalMethod,
+    /// The lower bound of the confidence interval.
+    pub lower: f64,
+    /// The upper bound of the confidence interval.
+    pub upper: f64,
+    /// The factor by which the frequency `count / iterations` is multiplied in the estimate.
+    pub scale: f64,
+}
+
+impl Estimate {
+    /// Estimates the probability of the event that happened in `count` out of `iterations`
+    /// iterations, with the interval of the given `confidence` level.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn new(count: usize, iterations: usize, confidence: f64, method: IntervalMethod) -> Self {
+        let k = count as f64;
+        let n = iterations as f64;
+        let probability = k / n;
+        let standard_error = (probability * (1.0 - probability) / n).sqrt();
+        let alpha = 1.0 - confidence;
+        let (lower, upper) = match method {
+            IntervalMethod::Wilson => {
+                let z = Normal::new(0.0, 1.0)
+                    .unwrap()
+                    .inverse_cdf(1.0 - alpha / 2.0);
+                let z2 = z * z;
+                let center = (probability + z2 / (2.0 * n)) / (1.0 + z2 / n);
+                let half_width = z / (1.0 + z2 / n)
+                    * (probability * (1.0 - probability) / n + z2 / (4.0 * n * n)).sqrt();
+                (
+                    (center - half_width).max(0.0),
+                    (center + half_width).min(1.0),
+                )
+            }
+            IntervalMethod::ClopperPearson => {
+                let lower = if count == 0 {
+                    0.0
+                } else {
+                    Beta::new(k, n - k + 1.0).unwrap().inverse_cdf(alpha / 2.0)
+                };
+                let upper = if count == iterations {
+                    1.0
+                } else {
+                    Beta::new(k + 1.0, n - k)
+                        .unwrap()
+                        .inverse_cdf(1.0 - alpha / 2.0)
+                };
+                (lower, upper)
+            }
+        };
+        Self {
+            count,
+            iterations,
+            probability,
+            standard_error,
+            confidence,
+            method,
+            lower,
+            upper,
+            scale: 1.0,
+        }
+    }
+}
+
+impl Estimate {
+    /// The estimate of twice the probability, capped at 1.
+    #[must_use]
+    pub fn doubled(self) -> Self {
+        Self {
+            probability: (2.0 * self.probability).min(1.0),
+            standard_error: 2.0 * self.standard_error,
+            lower: (2.0 * self.lower).min(1.0),
+            upper: (2.0 * self.upper).min(1.0),
+            scale: 2.0 * self.scale,
+            ..self
+        }
+    }
+}
+
+/// The precision at which an adaptive simulation stops.
+#[derive(Clone, Copy, Debug)]
+pub enum Target {
+    /// The standard error of the estimate is at most the value.
+    StandardError(f64),
+    /// The width of the confidence interval is at most the value.
+    Width(f64),
+}
+
+impl Target {
+    /// Checks whether `estimate` is precise enough.
+    ///
+    /// The standard error is calculated from the estimate `(count + 1) / (iterations + 2)`,
+    /// because the standard error of the plain estimate vanishes while nothing has been counted
+    /// yet.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn is_reached(self, estimate: &Estimate) -> bool {
+        match self {
+            Target::StandardError(target) => {
+                let n = estimate.iterations as f64 + 2.0;
+                let p = (estimate.count as f64 + 1.0) / n;
+                estimate.scale * (p * (1.0 - p) / n).sqrt() <= target
+            }
+            Target::Width(target) => estimate.upper - estimate.lower <= target,
+        }
+    }
+}
+
+/// Parses a number that satisfies `is_valid`.
+///
+/// The error message names the number by `what` and states the `requirement`, as in "the target
+/// precision must be positive".
+pub fn parse_number(
+    value: &str,
+    what: &str,
+    requirement: &str,
+    is_valid: impl Fn(f64) -> bool,
+) -> Result<f64, String> {
+    let number: f64 = value.parse().map_err(|err| format!("{err}"))?;
+    if is_valid(number) {
+        Ok(number)
+    } else {
+        Err(format!("{what} must {requirement}"))
+    }
+}
+
+/// Parses a probability like a confidence or a significance level, which must lie strictly
+/// between 0 and 1, named by `what` in the error message.
+pub fn parse_probability(value: &str, what: &str) -> Result<f64, String> {
+    parse_number(value, what, "lie between 0 and 1", |p| p > 0.0 && p < 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Asserts that `actual` is within the absolute `tolerance` of `expected`.
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{actual} is not within {tolerance} of {expected}"
+        );
+    }
+
+    /// The estimate of the probability of `tail` from `counts`.
+    fn estimate(tail: Tail, counts: &Counts, plus_one: bool) -> Estimate {
+        EstimateArg {
+            plus_one,
+            confidence: 0.95,
+            interval: IntervalMethod::Wilson,
+        }
+        .estimate(tail, counts)
+    }
+
+    /// The counts of 10 statistics, of which 1 is less than, 2 are equal to and 7 are greater
+    /// than the value.
+    const COUNTS: Counts = Counts {
+        less: 1,
+        equal: 2,
+        greater: 7,
+    };
+
+    #[test]
+    fn counts_ties_and_nan() {
+        let statistics = [2.0, 1.0, 2.0, f64::NAN, 3.0, 2.0];
+        let counts = Counts::new(&statistics, 2.0);
+        assert_eq!(
+            counts,
+            Counts {
+                less: 1,
+                equal: 3,
+                greater: 2
+            }
+        );
+        assert_eq!(counts.iterations(), statistics.len());
+        assert_eq!(Counts::new(&statistics, f64::NAN).greater, statistics.len());
+    }
+
+    #[test]
+    fn ties_belong_to_both_tails() {
+        let lower = estimate(Tail::Lower, &COUNTS, false);
+        assert_eq!((lower.count, lower.iterations), (3, 10));
+        assert_close(lower.probability, 0.3, 1e-15);
+        let upper = estimate(Tail::Upper, &COUNTS, false);
+        assert_eq!((upper.count, upper.iterations), (9, 10));
+        assert_close(upper.probability, 0.9, 1e-15);
+        let two_sided = estimate(Tail::TwoSided, &COUNTS, false);
+        assert_eq!(two_sided.count, 3);
+        assert_close(two_sided.probability, 0.6, 1e-15);
+        assert_close(two_sided.standard_error, 2.0 * lower.standard_error, 1e-15);
+    }
+
+    #[test]
+    fn plus_one_counts_the_tested_statistic() {
+        let lower = estimate(Tail::Lower, &COUNTS, true);
+        assert_eq!((lower.count, lower.iterations), (4, 11));
+        assert_close(lower.probability, 4.0 / 11.0, 1e-15);
+        let upper = estimate(Tail::Upper, &Counts::default().add(COUNTS), true);
+        assert_eq!((upper.count, upper.iterations), (10, 11));
+        let none = Counts {
+            less: 10,
+            equal: 0,
+            greater: 0,
+        };
+        assert_close(
+            estimate(Tail::Upper, &none, true).probability,
+            1.0 / 11.0,
+            1e-15,
+        );
+    }
+
+    #[test]
+    fn wilson_reference_values() {
+        let half = Estimate::new(5, 10, 0.95, IntervalMethod::Wilson);
+        assert_close(half.lower, 0.236_593, 1e-5);
+        assert_close(half.upper, 0.763_407, 1e-5);
+        let none = Estimate::new(0, 10, 0.95, IntervalMethod::Wilson);
+        assert_close(none.lower, 0.0, 1e-12);
+        assert_close(none.upper, 0.277_533, 1e-5);
+        let all = Estimate::new(10, 10, 0.95, IntervalMethod::Wilson);
+        assert_close(all.lower, 0.722_467, 1e-5);
+        assert_close(all.upper, 1.0, 1e-12);
+    }
+
+    #[test]
+    fn clopper_pearson_reference_values() {
+        let half = Estimate::new(5, 10, 0.95, IntervalMethod::ClopperPearson);
+        assert_close(half.lower, 0.187_086, 1e-5);
+        assert_close(half.upper, 0.812_914, 1e-5);
+        // With no or only successes, the bounds are `(alpha / 2)^(1 / n)` away from the edge
+        let edge = 0.025_f64.powf(0.1);
+        let none = Estimate::new(0, 10, 0.95, IntervalMethod::ClopperPearson);
+        assert_close(none.lower, 0.0, 0.0);
+        assert_close(none.upper, 1.0 - edge, 1e-9);
+        assert_close(none.standard_error, 0.0, 0.0);
+        let all = Estimate::new(10, 10, 0.95, IntervalMethod::ClopperPearson);
+        assert_close(all.lower, edge, 1e-9);
+        assert_close(all.upper, 1.0, 0.0);
+        assert_close(all.probability, 1.0, 0.0);
+    }
+
+    #[test]
+    fn two_sided_is_capped_at_one() {
+        let counts = Counts {
+            less: 4,
+            equal: 4,
+            greater: 2,
+        };
+        let two_sided = estimate(Tail::TwoSided, &counts, false);
+        assert_close(two_sided.probability, 1.0, 0.0);
+        assert!(two_sided.lower <= 1.0 && two_sided.upper <= 1.0);
+        assert_close(two_sided.upper, 1.0, 0.0);
+    }
+}
@@ -0,0 +1,412 @@
+// Copyright 2024 Vladimir Kharchev
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Estimation of the parameters of a family of distributions from a dataset.
+//!
+//! [`NormalFit`] is provided by [`monty_carlos`]; this module implements [`Fit`] for it and adds
+//! fitting types for the other families that are supported by the Lilliefors-style tests.
+
+use clap::ValueEnum;
+use monty_carlos::sample::fitting::NormalFit;
+use statrs::distribution::{ContinuousCDF, Exp, Gamma, Normal, Uniform, Weibull};
+use statrs::function::gamma::digamma;
+use statrs::statistics::{Distribution as Statistics, Max, Min};
+
+use crate::distribution::{Family, Null};
+use crate::statistic::mean;
+
+/// Maximal number of Newton iterations in the iterative estimators.
+const MAX_NEWTON_ITERATIONS: usize = 100;
+
+/// Relative tolerance at which the Newton iterations stop.
+const NEWTON_TOLERANCE: f64 = 1e-10;
+
+/// Estimates the parameters of a family of distributions.
+pub trait Fit {
+    /// The type of the fitted distribution.
+    type Distribution: ContinuousCDF<f64, f64>;
+
+    /// Fits the family to `data`.
+    ///
+    /// Returns `None` if `data` can't come from any member of the family, for example if it
+    /// contains a negative value and the family is supported on the positive half-line.
+    fn fit(&self, data: &[f64]) -> Option<Self::Distribution>;
+}
+
+/// Enum for the CLI option to choose the family that is fitted in a Lilliefors-style test.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FitFamily {
+    /// Fit a normal distribution by the sample mean and standard deviation.
+    Normal,
+    /// Fit an exponential distribution by maximum likelihood.
+    Exponential,
+    /// Fit a uniform distribution by the unbiased estimators of its bounds.
+    Uniform,
+    /// Fit a gamma distribution by maximum likelihood.
+    Gamma,
+    /// Fit a Weibull distribution by maximum likelihood.
+    Weibull,
+}
+
+impl FitFamily {
+    /// The name of the family as it is written on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            FitFamily::Normal => "normal",
+            FitFamily::Exponential => "exponential",
+            FitFamily::Uniform => "uniform",
+            FitFamily::Gamma => "gamma",
+            FitFamily::Weibull => "weibull",
+        }
+    }
+
+    /// The family of the null distribution whose members are fitted.
+    pub fn family(self) -> Family {
+        match self {
+            FitFamily::Normal => Family::Normal,
+            FitFamily::Exponential => Family::Exponential,
+            FitFamily::Uniform => Family::Uniform,
+            FitFamily::Gamma => Family::Gamma,
+            FitFamily::Weibull => Family::Weibull,
+        }
+    }
+
+    /// Checks that the distribution of a statistic of the fitted family doesn't depend on the
+    /// member from which the datasets are drawn.
+    ///
+    /// The location and scale families, and the Weibull family on the log scale, are invariant,
+    /// so any member of the family can simulate the statistic. The gamma family is not, because
+    /// the statistic depends on the shape.
+    pub fn is_invariant(self) -> bool {
+        !matches!(self, FitFamily::Gamma)
+    }
+
+    /// Checks that `null` has the support of the members of the family, so that the family can be
+    /// fitted to the datasets drawn from `null`.
+    pub fn supports(self, null: &Null) -> bool {
+        let (min, max) = (null.min(), null.max());
+        match self {
+            FitFamily::Normal => min.is_infinite() && max.is_infinite(),
+            FitFamily::Exponential | FitFamily::Gamma | FitFamily::Weibull => {
+                min >= 0.0 && max.is_infinite()
+            }
+            FitFamily::Uniform => min.is_finite() && max.is_finite(),
+        }
+    }
+}
+
+impl Fit for FitFamily {
+    type Distribution = Null;
+
+    /// Fits the family by its fitting type.
+    fn fit(&self, data: &[f64]) -> Option<Null> {
+        match self {
+            FitFamily::Normal => NormalFit.fit(data).map(Null::Normal),
+            FitFamily::Exponential => ExponentialFit.fit(data).map(Null::Exponential),
+            FitFamily::Uniform => UniformFit.fit(data).map(Null::Uniform),
+            FitFamily::Gamma => GammaFit.fit(data).map(Null::Gamma),
+            FitFamily::Weibull => WeibullFit.fit(data).map(Null::Weibull),
+        }
+    }
+}
+
+/// The names and the values of the parameters of a distribution fitted by [`FitFamily`].
+///
+/// The names are the ones of the command-line options of the family. A distribution of a family
+/// that can't be fitted has no parameters.
+pub fn fitted_parameters(fitted: &Null) -> Vec<(&'static str, f64)> {
+    match fitted {
+        Null::Normal(d) => vec![
+            ("mean", Statistics::mean(d).unwrap_or(f64::NAN)),
+            ("std-dev", Statistics::std_dev(d).unwrap_or(f64::NAN)),
+        ],
+        Null::Exponential(d) => vec![("rate", d.rate())],
+        Null::Uniform(d) => vec![("min", d.min()), ("max", d.max())],
+        Null::Gamma(d) => vec![("shape", d.shape()), ("rate", d.rate())],
+        Null::Weibull(d) => vec![("shape", d.shape()), ("scale", d.scale())],
+        Null::LogNormal(_) | Null::Beta(_) | Null::StudentsT(_) | Null::ChiSquared(_) => Vec::new(),
+    }
+}
+
+/// The mean of the logarithms of `data`.
+#[allow(clippy::cast_precision_loss)]
+fn log_mean(data: &[f64]) -> f64 {
+    data.iter().map(|x| x.ln()).sum::<f64>() / data.len() as f64
+}
+
+/// Checks that `data` is non-empty and consists of positive finite values.
+fn is_positive(data: &[f64]) -> bool {
+    !data.is_empty() && data.iter().all(|&x| x > 0.0 && x.is_finite())
+}
+
+/// The trigamma function, the derivative of [`digamma`].
+///
+/// Uses the recurrence relation to shift the argument above 6 and then the asymptotic expansion.
+fn trigamma(mut x: f64) -> f64 {
+    let mut result = 0.0;
+    while x < 6.0 {
+        result += 1.0 / (x * x);
+        x += 1.0;
+    }
+    let x2 = 1.0 / (x * x);
+    result
+        + 1.0 / x
+        + x2 / 2.0
+        + x2 / x * (1.0 / 6.0 - x2 * (1.0 / 30.0 - x2 * (1.0 / 42.0 - x2 / 30.0)))
+}
+
+impl Fit for NormalFit {
+    type Distribution = Normal;
+
+    #[allow(clippy::cast_precision_loss)]
+    fn fit(&self, data: &[f64]) -> Option<Normal> {
+        if data.len() < 2 {
+            return None;
+        }
+        let mean = mean(data);
+        let variance =
+            data.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (data.len() - 1) as f64;
+        Normal::new(mean, variance.sqrt()).ok()
+    }
+}
+
+/// Fits an exponential distribution by maximum likelihood.
+///
+/// The estimate of the rate is the reciprocal of the sample mean.
+#[derive(Clone, Copy, Debug)]
+pub struct ExponentialFit;
+
+impl Fit for ExponentialFit {
+    type Distribution = Exp;
+
+    fn fit(&self, data: &[f64]) -> Option<Exp> {
+        if !is_positive(data) {
+            return None;
+        }
+        Exp::new(1.0 / mean(data)).ok()
+    }
+}
+
+/// Fits a continuous uniform distribution.
+///
+/// The maximum-likelihood estimates of the bounds are the sample minimum and maximum, which always
+/// lie inside the true interval. They are widened by `(max - min) / (n - 1)` on each side, which
+/// makes them unbiased.
+#[derive(Clone, Copy, Debug)]
+pub struct UniformFit;
+
+impl Fit for UniformFit {
+    type Distribution = Uniform;
+
+    #[allow(clippy::cast_precision_loss)]
+    fn fit(&self, data: &[f64]) -> Option<Uniform> {
+        if data.len() < 2 {
+            return None;
+        }
+        let min = data.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = data.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let margin = (max - min) / (data.len() - 1) as f64;
+        Uniform::new(min - margin, max + margin).ok()
+    }
+}
+
+/// Fits a gamma distribution by maximum likelihood.
+///
+/// The shape is found by Newton's method, starting from the approximation of Minka (2002). The
+/// estimate of the rate is the shape divided by the sample mean.
+#[derive(Clone, Copy, Debug)]
+pub struct GammaFit;
+
+impl Fit for GammaFit {
+    type Distribution = Gamma;
+
+    fn fit(&self, data: &[f64]) -> Option<Gamma> {
+        if data.len() < 2 || !is_positive(data) {
+            return None;
+        }
+        let mean = mean(data);
+        let s = mean.ln() - log_mean(data);
+        if s <= 0.0 {
+            // All the values are equal
+            return None;
+        }
+        let mut shape = (3.0 - s + ((s - 3.0).powi(2) + 24.0 * s).sqrt()) / (12.0 * s);
+        for _ in 0..MAX_NEWTON_ITERATIONS {
+            let step = (shape.ln() - digamma(shape) - s) / (1.0 / shape - trigamma(shape));
+            shape -= step;
+            if step.abs() <= NEWTON_TOLERANCE * shape {
+                break;
+            }
+        }
+        Gamma::new(shape, shape / mean).ok()
+    }
+}
+
+/// Fits a Weibull distribution by maximum likelihood.
+///
+/// The shape is found by Newton's method from the profile likelihood equation, then the scale is
+/// expressed through the shape.
+#[derive(Clone, Copy, Debug)]
+pub struct WeibullFit;
+
+impl Fit for WeibullFit {
+    type Distribution = Weibull;
+
+    #[allow(clippy::cast_precision_loss)]
+    fn fit(&self, data: &[f64]) -> Option<Weibull> {
+        if data.len() < 2 || !is_positive(data) {
+            return None;
+        }
+        let n = data.len() as f64;
+        let log_mean = log_mean(data);
+        // The logarithm of the data, normalized by its maximum to avoid overflow in `x^k`
+        let log_max = data
+            .iter()
+            .map(|x| x.ln())
+            .fold(f64::NEG_INFINITY, f64::max);
+        let logs: Vec<f64> = data.iter().map(|x| x.ln() - log_max).collect();
+        let log_variance = logs
+            .iter()
+            .map(|l| (l + log_max - log_mean).powi(2))
+            .sum::<f64>()
+            / n;
+        if log_variance <= 0.0 {
+            // All the values are equal
+            return None;
+        }
+        // The shape of the Weibull distribution with the same variance of the logarithm
+        let mut shape = std::f64::consts::PI / (6.0 * log_variance).sqrt();
+        for _ in 0..MAX_NEWTON_ITERATIONS {
+            let (mut s0, mut s1, mut s2) = (0.0, 0.0, 0.0);
+            for &l in &logs {
+                let w = (shape * l).exp();
+                s0 += w;
+                s1 += w * l;
+                s2 += w * l * l;
+            }
+            // The likelihood equation for the shape is `g(k) = 0`, expressed in the normalized
+            // logarithms `l`
+            let g = s1 / s0 - 1.0 / shape - (log_mean - log_max);
+            let dg = (s2 * s0 - s1 * s1) / (s0 * s0) + 1.0 / (shape * shape);
+            let step = g / dg;
+            shape -= step;
+            if shape <= 0.0 {
+                return None;
+            }
+            if step.abs() <= NEWTON_TOLERANCE * shape {
+                break;
+            }
+        }
+        let sum: f64 = logs.iter().map(|l| (shape * l).exp()).sum();
+        let scale = log_max.exp() * (sum / n).powf(1.0 / shape);
+        Weibull::new(shape, scale).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use rand::distributions::Distribution;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha12Rng;
+
+    use super::*;
+
+    /// The number of values drawn to check that a fit recovers the parameters.
+    const LARGE_SAMPLE: usize = 100_000;
+
+    /// Asserts that `actual` is within the relative `tolerance` of `expected`.
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() <= tolerance * expected.abs(),
+            "{actual} is not within {tolerance} of {expected}"
+        );
+    }
+
+    /// Draws `n` values from `distribution` with a fixed seed.
+    fn draw<D: Distribution<f64>>(distribution: &D, n: usize) -> Vec<f64> {
+        let mut rng = ChaCha12Rng::seed_from_u64(1);
+        distribution.sample_iter(&mut rng).take(n).collect()
+    }
+
+    #[test]
+    fn trigamma_known_values() {
+        assert_close(trigamma(1.0), PI * PI / 6.0, 1e-12);
+        assert_close(trigamma(0.5), PI * PI / 2.0, 1e-12);
+        // ψ₁(10) = π²/6 - Σ 1/k² for k < 10, which doesn't use the shift of the argument
+        let expected = PI * PI / 6.0 - (1..10).map(|k| 1.0 / f64::from(k * k)).sum::<f64>();
+        assert_close(trigamma(10.0), expected, 1e-12);
+    }
+
+    #[test]
+    fn normal_fit_uses_sample_variance() {
+        let fitted = NormalFit.fit(&[1.0, 2.0, 3.0, 4.0]).unwrap();
+        assert_close(Statistics::mean(&fitted).unwrap(), 2.5, 1e-12);
+        assert_close(Statistics::variance(&fitted).unwrap(), 5.0 / 3.0, 1e-12);
+    }
+
+    #[test]
+    fn uniform_fit_widens_bounds() {
+        let fitted = UniformFit.fit(&[0.0, 1.0, 2.0, 3.0, 4.0]).unwrap();
+        assert_close(fitted.min(), -1.0, 1e-12);
+        assert_close(fitted.max(), 5.0, 1e-12);
+    }
+
+    #[test]
+    fn exponential_fit_recovers_rate() {
+        let data = draw(&Exp::new(2.0).unwrap(), LARGE_SAMPLE);
+        assert_close(ExponentialFit.fit(&data).unwrap().rate(), 2.0, 0.02);
+    }
+
+    #[test]
+    fn gamma_fit_recovers_parameters() {
+        let data = draw(&Gamma::new(2.5, 1.5).unwrap(), LARGE_SAMPLE);
+        let fitted = GammaFit.fit(&data).unwrap();
+        assert_close(fitted.shape(), 2.5, 0.03);
+        assert_close(fitted.rate(), 1.5, 0.03);
+    }
+
+    #[test]
+    fn weibull_fit_recovers_parameters() {
+        let data = draw(&Weibull::new(1.5, 2.0).unwrap(), LARGE_SAMPLE);
+        let fitted = WeibullFit.fit(&data).unwrap();
+        assert_close(fitted.shape(), 1.5, 0.03);
+        assert_close(fitted.scale(), 2.0, 0.03);
+    }
+
+    #[test]
+    fn fits_reject_impossible_data() {
+        assert!(NormalFit.fit(&[1.0]).is_none());
+        assert!(UniformFit.fit(&[1.0]).is_none());
+        assert!(ExponentialFit.fit(&[-1.0, 1.0]).is_none());
+        assert!(GammaFit.fit(&[1.0]).is_none());
+        assert!(GammaFit.fit(&[2.0, 2.0, 2.0]).is_none());
+        assert!(WeibullFit.fit(&[0.5, -0.5]).is_none());
+        assert!(WeibullFit.fit(&[2.0, 2.0, 2.0]).is_none());
+    }
+
+    #[test]
+    fn supports_compares_supports() {
+        let normal = Null::Normal(Normal::new(0.0, 1.0).unwrap());
+        let exponential = Null::Exponential(Exp::new(1.0).unwrap());
+        let uniform = Null::Uniform(Uniform::new(0.0, 1.0).unwrap());
+        assert!(FitFamily::Normal.supports(&normal));
+        assert!(!FitFamily::Normal.supports(&exponential));
+        assert!(FitFamily::Gamma.supports(&exponential));
+        assert!(!FitFamily::Gamma.supports(&normal));
+        assert!(FitFamily::Uniform.supports(&uniform));
+        assert!(!FitFamily::Uniform.supports(&exponential));
+    }
+}
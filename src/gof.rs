@@ -0,0 +1,289 @@
+// Copyright 2024 Vladimir Kharchev
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The goodness-of-fit tests that can be simulated.
+
+use clap::{error::ErrorKind, Args, ValueEnum};
+use monty_carlos::sample::Sample;
+
+use crate::distribution::{DistributionArg, Null};
+use crate::error::CliError;
+use crate::fitting::{Fit, FitFamily};
+use crate::sample::{AlternativeSample, FittedSample, KnownSample};
+use crate::statistic::Statistic;
+
+/// Enum for the CLI option to choose the goodness-of-fit test
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Test {
+    /// Run Kolmogorov-Smirnov test.
+    KolmogorovSmirnov,
+    /// Run Lilliefors test, fitting the family chosen by `--fit`.
+    Lilliefors,
+    /// Run Anderson-Darling test, fitting the family chosen by `--fit` if it is given.
+    AndersonDarling,
+    /// Run Cramér–von Mises test, fitting the family chosen by `--fit` if it is given.
+    CramerVonMises,
+    /// Run Watson U² test, fitting the family chosen by `--fit` if it is given.
+    Watson,
+}
+
+impl Test {
+    /// The name of the test as it is written on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            Test::KolmogorovSmirnov => "kolmogorov-smirnov",
+            Test::Lilliefors => "lilliefors",
+            Test::AndersonDarling => "anderson-darling",
+            Test::CramerVonMises => "cramer-von-mises",
+            Test::Watson => "watson",
+        }
+    }
+
+    /// The statistic calculated by the test.
+    pub fn statistic(self) -> Statistic {
+        match self {
+            Test::KolmogorovSmirnov | Test::Lilliefors => Statistic::KolmogorovSmirnov,
+            Test::AndersonDarling => Statistic::AndersonDarling,
+            Test::CramerVonMises => Statistic::CramerVonMises,
+            Test::Watson => Statistic::Watson,
+        }
+    }
+
+    /// The family fitted by the test, given the family chosen on the command line.
+    ///
+    /// Returns `None` if the null distribution is completely specified.
+    pub fn fit(self, fit: Option<FitFamily>) -> Option<FitFamily> {
+        match self {
+            Test::KolmogorovSmirnov => None,
+            Test::Lilliefors => Some(fit.unwrap_or(FitFamily::Normal)),
+            Test::AndersonDarling | Test::CramerVonMises | Test::Watson => fit,
+        }
+    }
+}
+
+/// The command-line arguments describing the null hypothesis of a test.
+#[derive(Args, Clone, Copy, Debug)]
+pub struct ModelArg {
+    /// The distribution from which the simulated datasets are drawn.
+    #[command(flatten)]
+    pub distribution: DistributionArg,
+    /// The family that is fitted to the simulated datasets.
+    ///
+    /// Lilliefors test fits the normal family by default. Anderson-Darling, Cramér–von Mises and
+    /// Watson tests use the null distribution as is unless a family is given. Without
+    /// `--distribution`, the datasets are drawn from the fitted family, whose shape has to be
+    /// given by `--shape` for the gamma family.
+    #[arg(long, value_enum)]
+    pub fit: Option<FitFamily>,
+}
+
+impl ModelArg {
+    /// Constructs the setup of `test` described by the arguments.
+    ///
+    /// Returns an error if the fitted family can't be fitted to the datasets drawn from the null
+    /// distribution, because their supports differ, or if the null distribution defaults to a
+    /// member of a fitted family whose statistic depends on the member, see
+    /// [`FitFamily::is_invariant`].
+    pub fn setup(&self, test: Test) -> Result<Setup, CliError> {
+        self.build_setup(test, false)
+    }
+
+    /// Like [`ModelArg::setup`], but for a parametric bootstrap, whose null distribution is
+    /// replaced by the member fitted to the data, so that any default member is accepted.
+    ///
+    /// Returns an error if `test` fits no family.
+    pub fn bootstrap_setup(&self, test: Test) -> Result<Setup, CliError> {
+        let setup = self.build_setup(test, true)?;
+        if setup.fit.is_none() {
+            return Err(clap::Error::raw(
+                ErrorKind::ArgumentConflict,
+                "--bootstrap requires a fitted family, use --fit or the Lilliefors test\n",
+            )
+            .into());
+        }
+        Ok(setup)
+    }
+
+    /// Constructs the setup of `test`, accepting the default member of any fitted family if
+    /// `bootstrap` is set.
+    fn build_setup(&self, test: Test, bootstrap: bool) -> Result<Setup, CliError> {
+        if self.fit.is_some() && test == Test::KolmogorovSmirnov {
+            return Err(clap::Error::raw(
+                ErrorKind::ArgumentConflict,
+                "--fit can't be used with the Kolmogorov-Smirnov test, use the Lilliefors test\n",
+            )
+            .into());
+        }
+        let fit = test.fit(self.fit);
+        let mut distribution = self.distribution;
+        if let (None, Some(fit)) = (distribution.distribution, fit) {
+            if !bootstrap && !fit.is_invariant() && distribution.shape.is_none() {
+                return Err(CliError::Parameter(format!(
+                    "the distribution of the statistic with the fitted {0} family depends on the \
+                     shape of the {0} distribution, give the null distribution by --shape or \
+                     --distribution, or use --bootstrap with --data",
+                    fit.name()
+                )));
+            }
+            distribution.distribution = Some(fit.family());
+        }
+        let null = distribution.build().map_err(CliError::Parameter)?;
+        if let Some(fit) = fit.filter(|fit| !fit.supports(&null)) {
+            return Err(CliError::Parameter(format!(
+                "the {} family can't be fitted to datasets drawn from the {} distribution, \
+                 whose support differs",
+                fit.name(),
+                distribution.family().name()
+            )));
+        }
+        Ok(Setup {
+            test,
+            distribution,
+            null,
+            fit,
+        })
+    }
+}
+
+/// The message of the panic if a sample that was created before can't be created again.
+const RECREATED: &str = "the sample was created with the same arguments";
+
+/// A goodness-of-fit test together with its null hypothesis.
+#[derive(Clone, Copy, Debug)]
+pub struct Setup {
+    /// The test.
+    pub test: Test,
+    /// The null distribution as it is described on the command line, with the family chosen by
+    /// `--fit` if no family is given.
+    pub distribution: DistributionArg,
+    /// The distribution from which the datasets are drawn under the null hypothesis.
+    pub null: Null,
+    /// The family fitted by the test, if any.
+    pub fit: Option<FitFamily>,
+}
+
+/// Checks that datasets of size `samples` can be simulated by `setup`.
+///
+/// A fitted family needs at least two values, because no family can be fitted to a single value.
+pub fn check_samples(setup: &Setup, samples: usize) -> Result<(), CliError> {
+    if samples == 0 {
+        return Err(CliError::Parameter(format!(
+            "the sample size must be positive, got {samples}"
+        )));
+    }
+    if let (Some(fit), true) = (setup.fit, samples < 2) {
+        return Err(CliError::Parameter(format!(
+            "the {} family can't be fitted to datasets of size {samples}",
+            fit.name()
+        )));
+    }
+    Ok(())
+}
+
+/// Checks that a simulation of `iterations` iterations produces any statistics.
+pub fn check_iterations(iterations: usize) -> Result<(), CliError> {
+    if iterations == 0 {
+        return Err(CliError::Parameter(
+            "the number of iterations must be positive".to_owned(),
+        ));
+    }
+    Ok(())
+}
+
+impl Setup {
+    /// Creates the sample of the test for datasets of size `samples`.
+    ///
+    /// Returns an error if `samples` is rejected by [`check_samples`].
+    pub fn sample(&self, samples: usize) -> Result<Box<dyn Sample>, CliError> {
+        check_samples(self, samples)?;
+        let invalid = || CliError::Parameter(format!("invalid sample size {samples}"));
+        let (null, statistic) = (self.null, self.test.statistic());
+        // The simulated statistics are calculated by the same code as the statistic of a dataset
+        // in `Setup::statistic`
+        let sample: Box<dyn Sample> = match self.fit {
+            None => Box::new(KnownSample::new(null, samples, statistic).ok_or_else(invalid)?),
+            Some(fit) => {
+                Box::new(FittedSample::new(null, samples, fit, statistic).ok_or_else(invalid)?)
+            }
+        };
+        Ok(sample)
+    }
+
+    /// Creates the sample of the test for datasets of size `samples` drawn from `alternative`
+    /// instead of the null distribution.
+    ///
+    /// Returns an error if `samples` is rejected by [`check_samples`].
+    pub fn alternative_sample(
+        &self,
+        alternative: Null,
+        samples: usize,
+    ) -> Result<Box<dyn Sample>, CliError> {
+        match self.fit {
+            // With a fitted family, the null distribution only generates the datasets
+            Some(_) => Setup {
+                null: alternative,
+                ..*self
+            }
+            .sample(samples),
+            None => {
+                check_samples(self, samples)?;
+                let sample =
+                    AlternativeSample::new(alternative, self.null, samples, self.test.statistic())
+                        .ok_or_else(|| {
+                            CliError::Parameter(format!("invalid sample size {samples}"))
+                        })?;
+                Ok(Box::new(sample))
+            }
+        }
+    }
+
+    /// The function creating the samples of a simulation of the test for datasets of size
+    /// `samples`.
+    ///
+    /// The sample is created once here, so that an error is returned before the simulation
+    /// starts. The creation depends only on the setup and the size, so it succeeds again for
+    /// every chunk of the simulation.
+    pub fn sampler(&self, samples: usize) -> Result<impl Fn() -> Box<dyn Sample> + Sync, CliError> {
+        self.sample(samples)?;
+        let setup = *self;
+        Ok(move || setup.sample(samples).expect(RECREATED))
+    }
+
+    /// Like [`Setup::sampler`], but with the datasets drawn from `alternative` as in
+    /// [`Setup::alternative_sample`].
+    pub fn alternative_sampler(
+        &self,
+        alternative: Null,
+        samples: usize,
+    ) -> Result<impl Fn() -> Box<dyn Sample> + Sync, CliError> {
+        self.alternative_sample(alternative, samples)?;
+        let setup = *self;
+        Ok(move || {
+            setup
+                .alternative_sample(alternative, samples)
+                .expect(RECREATED)
+        })
+    }
+
+    /// Calculates the statistic of the test for the `sorted` dataset.
+    ///
+    /// Returns `None` if the fitted family can't be fitted to the dataset.
+    pub fn statistic(&self, sorted: &[f64]) -> Option<f64> {
+        let statistic = self.test.statistic();
+        match self.fit {
+            None => Some(statistic.evaluate(sorted, &self.null)),
+            Some(fit) => Some(statistic.evaluate(sorted, &fit.fit(sorted)?)),
+        }
+    }
+}
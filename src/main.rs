@@ -14,20 +14,50 @@
 
 //! A simple CLI program that uses [`monty_carlos`] crate to run Monte-Carlo simulations.
 #![warn(clippy::pedantic)]
-use clap::{Args, Parser, ValueEnum};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
 
-use monty_carlos::sample::{fitting::NormalFit, KSSample, LillieforsSample, Sample};
-use monty_carlos::MonteCarlo;
-use statrs::distribution::Normal;
+use clap::{error::ErrorKind, Args, Parser, Subcommand};
+use monty_carlos::sample::Sample;
 
-/// Enum for the CLI option to choose Kolmogorov-Smirnov or Lilliefors test
-#[derive(ValueEnum, Clone, Copy)]
-enum Test {
-    /// Run Kolmogorov-Smirnov test.
-    KolmogorovSmirnov,
-    /// Run Lilliefors test.
-    Lilliefors,
-}
+use batch::BatchArg;
+use cache::{CacheAction, CacheArg};
+use calibrate::CalibrateArg;
+use empirical::{Histogram, Summary};
+use error::CliError;
+use estimate::{Counts, PValueArg, Tail, Target};
+use fitting::{Fit, FitFamily};
+use gof::{ModelArg, Setup, Test};
+use output::{Format, Metadata, Outcome};
+use permutation::PermutationArg;
+use plan::PlanArg;
+use power::PowerArg;
+use simulation::{RunArg, Simulation};
+use table::TableArg;
+use two_sample::TwoSampleArg;
+
+mod batch;
+mod cache;
+mod calibrate;
+mod data;
+mod distribution;
+mod empirical;
+mod error;
+mod estimate;
+mod fitting;
+mod gof;
+mod output;
+mod permutation;
+mod plan;
+mod power;
+mod progress;
+mod sample;
+mod simulation;
+mod statistic;
+mod store;
+mod sweep;
+mod table;
+mod two_sample;
 
 /// The enum [`SimulationType`] in the form required by [clap].
 ///
@@ -36,15 +66,35 @@ enum Test {
 /// false]` ensures that any instance of [`SimulationTypeArg`] created by [clap] is a member of a
 /// subtype, that is isomorphic to [`SimulationType`]. [`SimulationTypeArg::condence`] is this
 /// isomorphism.
-#[derive(Args, Clone, Copy)]
+#[derive(Args, Clone)]
 #[group(required = true, multiple = false)]
 struct SimulationTypeArg {
     #[arg(long)]
-    /// Calculate the probability that the statistic is less than the given value.
+    /// Calculate the probability that the statistic is in the tail (chosen by `--tail`) of the
+    /// given value.
     test_statistic: Option<f64>,
     #[arg(long)]
     /// Output the distribution of statistics in the simulation.
     make_distribution: bool,
+    #[arg(long, value_name = "PATH")]
+    /// Calculate the statistic of the dataset in the file (`-` for the standard input) and the
+    /// probability that the statistic is in its tail (the upper one unless `--tail` is given),
+    /// which is the p-value of the goodness-of-fit test.
+    data: Option<PathBuf>,
+    #[arg(long, value_name = "Q", value_parser = parse_order)]
+    /// Output the empirical quantile of order Q of the statistics in the simulation. Can be given
+    /// several times.
+    quantile: Vec<f64>,
+}
+
+/// Parses the order of a quantile, which must lie between 0 and 1.
+fn parse_order(value: &str) -> Result<f64, String> {
+    estimate::parse_number(
+        value,
+        "the order of a quantile",
+        "lie between 0 and 1",
+        |q| (0.0..=1.0).contains(&q),
+    )
 }
 
 impl SimulationTypeArg {
@@ -52,6 +102,10 @@ impl SimulationTypeArg {
     fn condence(self) -> SimulationType {
         if let Some(test_statistic) = self.test_statistic {
             SimulationType::TestStatistic(test_statistic)
+        } else if let Some(path) = self.data {
+            SimulationType::Data(path)
+        } else if !self.quantile.is_empty() {
+            SimulationType::Quantiles(self.quantile)
         } else {
             SimulationType::MakeDistribution
         }
@@ -60,52 +114,489 @@ impl SimulationTypeArg {
 
 /// Describes which result is requested from the simulation.
 enum SimulationType {
-    /// Probability that the test statistic is less than the value.
+    /// Probability that the test statistic is in the tail of the value.
     TestStatistic(f64),
     /// The distribution of statistics in the simulation.
     MakeDistribution,
+    /// Probability that the test statistic is in the tail of the statistic of the dataset in the
+    /// file.
+    Data(PathBuf),
+    /// The empirical quantiles of the given orders of the statistics in the simulation.
+    Quantiles(Vec<f64>),
+}
+
+/// The enum [`Target`] in the form required by [clap].
+///
+/// Like [`SimulationTypeArg`], except that the group is not required, so
+/// [`TargetArg::condence`] returns `None` if no target is given.
+#[derive(Args, Clone, Copy)]
+#[group(multiple = false)]
+struct TargetArg {
+    /// Keep simulating until the standard error of the p-value is at most the value.
+    #[arg(long, value_name = "EPS", value_parser = parse_target)]
+    target_se: Option<f64>,
+    /// Keep simulating until the width of the confidence interval of the p-value is at most the
+    /// value.
+    #[arg(long, value_name = "WIDTH", value_parser = parse_target)]
+    target_ci_width: Option<f64>,
+}
+
+/// Parses a target precision, which must be positive.
+fn parse_target(value: &str) -> Result<f64, String> {
+    estimate::parse_number(value, "the target precision", "be positive", |x| x > 0.0)
+}
+
+impl TargetArg {
+    /// The isomorphism from [`TargetArg`] to `Option<Target>`
+    fn condence(self) -> Option<Target> {
+        match (self.target_se, self.target_ci_width) {
+            (Some(target), _) => Some(Target::StandardError(target)),
+            (None, Some(target)) => Some(Target::Width(target)),
+            (None, None) => None,
+        }
+    }
+}
+
+/// The subcommands of the program.
+#[derive(Subcommand)]
+enum Command {
+    /// Generate a table of critical values for several sample sizes and significance levels.
+    Table(TableArg),
+    /// Test whether two datasets come from the same continuous distribution by the two-sample
+    /// Kolmogorov-Smirnov test.
+    TwoSample(TwoSampleArg),
+    /// Test whether two groups of labelled values differ by a permutation test.
+    Permutation(PermutationArg),
+    /// Simulate the power of a test against an alternative distribution.
+    Power(PowerArg),
+    /// Find the smallest sample size at which a test reaches a target power against an
+    /// alternative distribution.
+    Plan(PlanArg),
+    /// Check that the simulated p-values of a test are uniformly distributed under the null
+    /// hypothesis.
+    Calibrate(CalibrateArg),
+    /// Run the simulations described by a spec file and write their combined results.
+    Batch(BatchArg),
+    /// Manage the cache of simulated distributions.
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
 }
 
 /// The struct for command-line arguments.
+///
+/// Without a subcommand, the program runs a single simulation described by the arguments.
 #[derive(Parser)]
 #[command(about = "Runs Monte-Carlo simulations", long_about = None)]
+#[command(allow_missing_positional = true)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Cli {
-    /// The size of simulated datasets of Kolmogorov-Smirnov or Lilliefors test.
-    samples: usize,
+    /// The subcommand to run instead of a single simulation.
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// The size of simulated datasets of the test.
+    ///
+    /// Must be omitted with `--data`, which takes the size of the dataset in the file, and may be
+    /// omitted with `--load-distribution`, which takes the size of the saved simulation.
+    #[arg(
+        required_unless_present_any = ["data", "load_distribution"],
+        conflicts_with = "data"
+    )]
+    samples: Option<usize>,
     /// Number of iterations of the simulation.
-    #[arg(long)]
+    #[arg(long, conflicts_with = "TargetArg")]
     iterations: Option<usize>,
+    /// The precision at which the simulation of the p-value stops.
+    #[command(flatten)]
+    target: TargetArg,
+    /// Maximal number of iterations of the simulation with a target precision.
+    ///
+    /// If the target isn't reached within these iterations, a warning is printed and the output
+    /// reports that the target is not reached.
+    #[arg(long, default_value_t = 10_000_000, requires = "TargetArg")]
+    max_iterations: usize,
+    /// Run a parametric bootstrap: simulate the datasets from the distribution fitted to the data
+    /// instead of the null distribution.
+    ///
+    /// Every simulated dataset is fitted again, so the p-value accounts for the estimation of the
+    /// parameters even if the distribution of the statistic depends on their values.
+    #[arg(long, requires = "data")]
+    bootstrap: bool,
+    /// Save the simulated statistics to the file, so that later queries can be answered by
+    /// `--load-distribution` without simulating them again.
+    #[arg(long, value_name = "FILE", conflicts_with = "TargetArg")]
+    save_distribution: Option<PathBuf>,
+    /// Answer the query from the statistics saved by `--save-distribution` instead of simulating
+    /// them.
+    ///
+    /// The test and the sample size must be the ones of the saved simulation, and so must the null
+    /// distribution, its parameters and the fitted family if they are given. With `--data`, they
+    /// must be the saved ones even if they are not given. A distribution simulated by
+    /// `--bootstrap` can't be loaded.
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["iterations", "TargetArg", "bootstrap", "seed", "save_distribution"]
+    )]
+    load_distribution: Option<PathBuf>,
+    /// The seed and the number of threads of the simulation.
+    #[command(flatten)]
+    run: RunArg,
+    /// How the p-value is estimated.
+    #[command(flatten)]
+    pvalue: PValueArg,
+    /// The cache of simulated distributions.
+    #[command(flatten)]
+    cache: CacheArg,
+    /// The format of the output.
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+    /// Output the mean, variance, skewness, excess kurtosis, extremes and selected quantiles of
+    /// the distribution instead of the statistics.
+    #[arg(long, requires = "make_distribution")]
+    summary: bool,
+    /// Output a histogram of the distribution with the given number of bins of equal width
+    /// instead of the statistics.
+    #[arg(long, value_name = "BINS", requires = "make_distribution")]
+    histogram: Option<NonZeroUsize>,
+    /// Draw the histogram as a bar chart in the text output.
+    #[arg(long, requires = "histogram")]
+    bars: bool,
     /// Which result should be produced.
     #[command(flatten)]
     simulation_type: SimulationTypeArg,
+    /// The null hypothesis of the test.
+    #[command(flatten, next_help_heading = "Null distribution")]
+    model: ModelArg,
     /// The statistical test to be simulated.
-    #[arg(value_enum)]
-    test: Test,
+    ///
+    /// It is always present without a subcommand.
+    #[arg(value_enum, required = true)]
+    test: Option<Test>,
 }
 
-fn main() {
-    let cli = Cli::parse();
-    let sample: Box<dyn Sample> = match cli.test {
-        Test::KolmogorovSmirnov => {
-            Box::new(KSSample::new(Normal::new(0.0, 1.0).unwrap(), cli.samples).unwrap())
-        }
-        Test::Lilliefors => Box::new(
-            LillieforsSample::new(Normal::new(0.0, 1.0).unwrap(), cli.samples, NormalFit).unwrap(),
-        ),
+/// Runs the single simulation described by the command-line arguments and prints its result.
+fn simulate(cli: Cli) -> Result<(), CliError> {
+    let format = cli.format;
+    let (metadata, outcome) = run_simulation(cli)?;
+    Ok(output::print(format, &metadata, &outcome)?)
+}
+
+/// Checks that the saved simulation described by `saved` answers the query of `setup` for datasets
+/// of size `samples`.
+///
+/// The null distribution and the fitted family must agree with the saved simulation if they are
+/// given in `model`. With `has_data`, the statistic of a dataset is calculated by `setup`, so they
+/// must agree even if they are not given. A parametric bootstrap is never reused, because its
+/// distribution depends on the dataset to which the family was fitted.
+fn check_saved(
+    saved: &Metadata,
+    setup: &Setup,
+    model: &ModelArg,
+    samples: usize,
+    has_data: bool,
+) -> Result<(), CliError> {
+    let mismatch = |what: String| {
+        Err(clap::Error::raw(
+            ErrorKind::ArgumentConflict,
+            format!("the saved distribution was simulated {what}\n"),
+        )
+        .into())
     };
-    let mut simulator = MonteCarlo::new(sample);
-    if let Some(iterations) = cli.iterations {
-        simulator.iterations = iterations;
+    if saved.bootstrap {
+        return mismatch("by a parametric bootstrap of another dataset".to_owned());
+    }
+    if saved.test != setup.test.name() {
+        return mismatch(format!("for the {} test", saved.test));
+    }
+    if saved.samples != samples {
+        return mismatch(format!("for datasets of size {}", saved.samples));
+    }
+    let fit = setup.fit.map(FitFamily::name);
+    if (has_data || model.fit.is_some()) && saved.fit != fit {
+        return mismatch(match saved.fit {
+            Some(fit) => format!("with --fit {fit}"),
+            None => "without a fitted family".to_owned(),
+        });
+    }
+    let null = (
+        setup.distribution.family().name(),
+        setup.distribution.parameters(),
+    );
+    let compares_null = model.distribution.is_given() || (has_data && fit.is_none());
+    if compares_null && (saved.distribution, saved.parameters.clone()) != null {
+        return mismatch(format!(
+            "from the {} distribution with other parameters",
+            saved.distribution
+        ));
+    }
+    Ok(())
+}
+
+/// Calculates the statistic of the dataset in the file at `path` and returns it together with the
+/// size of the dataset.
+///
+/// With `--bootstrap`, the null distribution of `setup` is replaced by the distribution fitted to
+/// the dataset. If the distribution was simulated before as described by `saved`, the dataset must
+/// agree with the saved simulation.
+fn data_statistic(
+    path: &Path,
+    setup: &mut Setup,
+    cli: &Cli,
+    saved: Option<&Metadata>,
+) -> Result<(usize, f64), CliError> {
+    let mut data = data::read(path)?;
+    data.sort_unstable_by(f64::total_cmp);
+    if let Some(saved) = saved {
+        check_saved(saved, setup, &cli.model, data.len(), true)?;
     }
+    let statistic = setup.statistic(&data).ok_or_else(|| {
+        let family = setup.fit.map_or("null", FitFamily::name);
+        CliError::Numerical(format!("the {family} family can't be fitted to the data"))
+    })?;
+    if let (true, Some(fit)) = (cli.bootstrap, setup.fit) {
+        setup.null = fit.fit(&data).expect("the family was fitted to the data");
+    }
+    Ok((data.len(), statistic))
+}
 
-    match cli.simulation_type.condence() {
-        SimulationType::TestStatistic(test_statistic) => {
-            let pvalue = simulator.simulate_pvalue(test_statistic);
-            println!("pvalue = {pvalue}");
+/// Produces the result requested by `simulation_type` from the simulated `statistics`, or by
+/// `simulator` if the statistics are not needed.
+fn make_outcome<F: Fn() -> Box<dyn Sample> + Sync>(
+    cli: &Cli,
+    simulation_type: SimulationType,
+    tail: Tail,
+    statistics: Option<&[f64]>,
+    simulator: &mut Simulation<F>,
+    target: Option<Target>,
+) -> Outcome {
+    let sorted = || {
+        let mut sorted = statistics.expect("the statistics are simulated").to_vec();
+        sorted.sort_unstable_by(f64::total_cmp);
+        sorted
+    };
+    match simulation_type {
+        SimulationType::TestStatistic(statistic) => {
+            let (counts, target_reached) = match (statistics, target) {
+                (Some(statistics), _) => (Counts::new(statistics, statistic), None),
+                (None, Some(target)) => {
+                    let (counts, reached) = simulator.count_until(statistic, |counts| {
+                        target.is_reached(&cli.pvalue.estimate(tail, counts))
+                    });
+                    if !reached {
+                        eprintln!(
+                            "warning: the target precision is not reached in the maximum of {} \
+                             iterations",
+                            simulator.iterations
+                        );
+                    }
+                    (counts, Some(reached))
+                }
+                (None, None) => (simulator.count(statistic), None),
+            };
+            Outcome::PValue {
+                statistic,
+                tail,
+                pvalue: cli.pvalue.estimate(tail, &counts),
+                target_reached,
+            }
+        }
+        SimulationType::MakeDistribution if cli.summary || cli.histogram.is_some() => {
+            let distribution = sorted();
+            Outcome::Description {
+                summary: cli.summary.then(|| Summary::new(&distribution)),
+                histogram: cli
+                    .histogram
+                    .map(|bins| Histogram::new(&distribution, bins)),
+                bars: cli.bars,
+            }
         }
         SimulationType::MakeDistribution => {
-            let distr = simulator.simulate_distribution();
-            println!("{distr:?}");
+            Outcome::Distribution(statistics.expect("the statistics are simulated").to_vec())
         }
+        SimulationType::Quantiles(orders) => {
+            let distribution = sorted();
+            Outcome::Quantiles(
+                orders
+                    .into_iter()
+                    .map(|q| (q, empirical::quantile(&distribution, q)))
+                    .collect(),
+            )
+        }
+        SimulationType::Data(_) => unreachable!("the dataset is replaced by its statistic"),
+    }
+}
+
+/// The description of the simulation of `setup` with the given parameters.
+fn describe_simulation(
+    cli: &Cli,
+    setup: &Setup,
+    samples: usize,
+    iterations: usize,
+    seed: u64,
+    data: Option<PathBuf>,
+) -> Metadata {
+    Metadata {
+        test: setup.test.name(),
+        samples,
+        iterations,
+        distribution: match setup.fit {
+            Some(fit) if cli.bootstrap => fit.name(),
+            _ => setup.distribution.family().name(),
+        },
+        parameters: if cli.bootstrap {
+            fitting::fitted_parameters(&setup.null)
+        } else {
+            setup.distribution.parameters()
+        },
+        bootstrap: cli.bootstrap,
+        fit: setup.fit.map(FitFamily::name),
+        seed,
+        data,
+        target: cli.target.condence(),
+    }
+}
+
+/// Saves the `statistics` of the simulation described by `metadata` to the file given by
+/// `--save-distribution` and to the `cache_entry`.
+///
+/// A failure to cache the statistics is only reported as a warning.
+fn keep_statistics(
+    cli: &Cli,
+    metadata: &Metadata,
+    statistics: &[f64],
+    cache_entry: Option<&Path>,
+) -> Result<(), CliError> {
+    if let Some(path) = &cli.save_distribution {
+        store::save(path, metadata, statistics)?;
+    }
+    if let Some(entry) = cache_entry {
+        if let Err(err) = cache::insert(entry, metadata, statistics) {
+            eprintln!("warning: the distribution is not cached: {err}");
+        }
+    }
+    Ok(())
+}
+
+/// Runs the single simulation described by the command-line arguments.
+///
+/// With `--load-distribution`, the statistics are loaded instead of simulated. With
+/// `--save-distribution`, the statistics are simulated even if only their comparison with the
+/// tested statistic is needed, so that they can be saved.
+fn run_simulation(cli: Cli) -> Result<(Metadata, Outcome), CliError> {
+    let test = cli.test.expect("the test is required without a subcommand");
+    let mut setup = if cli.bootstrap {
+        cli.model.bootstrap_setup(test)?
+    } else {
+        cli.model.setup(test)?
+    };
+    let saved = match &cli.load_distribution {
+        Some(path) => Some(store::load(path)?),
+        None => None,
+    };
+    let mut data_path = None;
+    let mut tail = cli.pvalue.tail.unwrap_or(Tail::Lower);
+    let (samples, simulation_type) = match cli.simulation_type.clone().condence() {
+        SimulationType::Data(path) => {
+            // The p-value of a goodness-of-fit test is the upper tail
+            tail = cli.pvalue.tail.unwrap_or(Tail::Upper);
+            let saved = saved.as_ref().map(|saved| &saved.metadata);
+            let (samples, statistic) = data_statistic(&path, &mut setup, &cli, saved)?;
+            data_path = Some(path);
+            (samples, SimulationType::TestStatistic(statistic))
+        }
+        simulation_type => {
+            let samples = cli
+                .samples
+                .or(saved.as_ref().map(|saved| saved.metadata.samples))
+                .expect("the sample size is required without --data or --load-distribution");
+            if let Some(saved) = &saved {
+                check_saved(&saved.metadata, &setup, &cli.model, samples, false)?;
+            }
+            (samples, simulation_type)
+        }
+    };
+    let target = cli.target.condence();
+    if target.is_some() && !matches!(simulation_type, SimulationType::TestStatistic(_)) {
+        return Err(clap::Error::raw(
+            ErrorKind::ArgumentConflict,
+            "a target precision can only be used with --test-statistic or --data\n",
+        )
+        .into());
+    }
+    let seed = saved
+        .as_ref()
+        .map_or_else(|| cli.run.seed(), |saved| saved.metadata.seed);
+    let mut simulator = Simulation::new(
+        setup.sampler(samples)?,
+        seed,
+        match (&saved, target) {
+            (Some(saved), _) => Some(saved.metadata.iterations),
+            (None, Some(_)) => Some(cli.max_iterations),
+            (None, None) => cli.iterations,
+        },
+        &cli.run,
+    );
+    gof::check_iterations(simulator.iterations)?;
+    // Only the simulations with a given seed and a fixed number of iterations are repeatable
+    let is_repeatable = cli.run.seed.is_some() && target.is_none() && !cli.bootstrap;
+    let cache_entry = (saved.is_none() && is_repeatable)
+        .then(|| {
+            let iterations = simulator.iterations;
+            cli.cache.entry(&describe_simulation(
+                &cli, &setup, samples, iterations, seed, None,
+            ))
+        })
+        .flatten();
+    let cached = cache_entry.as_deref().and_then(cache::lookup);
+    let is_cached = cached.is_some();
+    let (saved_metadata, saved_statistics) = saved
+        .or(cached)
+        .map(|saved| (saved.metadata, saved.statistics))
+        .unzip();
+    let needs_statistics = saved_statistics.is_some()
+        || cli.save_distribution.is_some()
+        || cache_entry.is_some()
+        || !matches!(simulation_type, SimulationType::TestStatistic(_));
+    let statistics = needs_statistics
+        .then(|| saved_statistics.unwrap_or_else(|| simulator.simulate_distribution()));
+    let outcome = make_outcome(
+        &cli,
+        simulation_type,
+        tail,
+        statistics.as_deref(),
+        &mut simulator,
+        target,
+    );
+    let metadata = match saved_metadata {
+        Some(saved) => Metadata {
+            data: data_path,
+            ..saved
+        },
+        None => describe_simulation(&cli, &setup, samples, simulator.iterations, seed, data_path),
+    };
+    if let Some(statistics) = &statistics {
+        let cache_entry = cache_entry.as_deref().filter(|_| !is_cached);
+        keep_statistics(&cli, &metadata, statistics, cache_entry)?;
+    }
+    Ok((metadata, outcome))
+}
+
+fn main() {
+    let mut cli = Cli::parse();
+    let result = match cli.command.take() {
+        Some(Command::Table(args)) => table::run(&args),
+        Some(Command::TwoSample(args)) => two_sample::run(&args),
+        Some(Command::Permutation(args)) => permutation::run(&args),
+        Some(Command::Power(args)) => power::run(&args),
+        Some(Command::Plan(args)) => plan::run(&args),
+        Some(Command::Calibrate(args)) => calibrate::run(&args),
+        Some(Command::Batch(args)) => batch::run(&args),
+        Some(Command::Cache { action }) => cache::run(&action),
+        None => simulate(cli),
+    };
+    if let Err(err) = result {
+        err.exit();
     }
 }
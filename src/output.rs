@@ -0,0 +1,538 @@
+// Copyright 2024 Vladimir Kharchev
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Printing the results of simulations in the format chosen on the command line.
+
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use clap::ValueEnum;
+use serde_json::{json, Map, Value};
+
+use crate::empirical::{Histogram, Summary};
+use crate::estimate::{Estimate, Tail, Target};
+
+/// The number of characters in the longest bar of a histogram drawn as a bar chart.
+const BAR_WIDTH: usize = 50;
+
+/// Enum for the CLI option to choose the output format.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// Human-readable text.
+    Text,
+    /// A JSON object with the description of the simulation and its result.
+    Json,
+    /// Comma-separated values with a header row.
+    Csv,
+    /// Tab-separated values with a header row.
+    Tsv,
+}
+
+impl Format {
+    /// The separator of the fields in a row of a table.
+    ///
+    /// Returns `None` if the format is not a table.
+    pub fn separator(self) -> Option<&'static str> {
+        match self {
+            Format::Text | Format::Json => None,
+            Format::Csv => Some(","),
+            Format::Tsv => Some("\t"),
+        }
+    }
+}
+
+/// The description of a simulation that is reported together with its result.
+pub struct Metadata {
+    /// The name of the test.
+    pub test: &'static str,
+    /// The size of the simulated datasets.
+    pub samples: usize,
+    /// The number of iterations of the simulation.
+    pub iterations: usize,
+    /// The name of the family of the null distribution.
+    pub distribution: &'static str,
+    /// The names and the values of the parameters of the null distribution.
+    pub parameters: Vec<(&'static str, f64)>,
+    /// The name of the family fitted by the test, if any.
+    pub fit: Option<&'static str>,
+    /// Whether the datasets were drawn from the distribution fitted to the data.
+    pub bootstrap: bool,
+    /// The seed of the random number generator.
+    pub seed: u64,
+    /// The file of the dataset whose statistic was tested, if any.
+    pub data: Option<PathBuf>,
+    /// The precision at which the simulation stopped, if it was adaptive.
+    pub target: Option<Target>,
+}
+
+impl Metadata {
+    /// The JSON object with the fields of the metadata.
+    pub fn to_json(&self) -> Map<String, Value> {
+        let mut object = Map::new();
+        object.insert("test".to_owned(), json!(self.test));
+        object.insert("samples".to_owned(), json!(self.samples));
+        object.insert("iterations".to_owned(), json!(self.iterations));
+        object.insert(
+            "distribution".to_owned(),
+            distribution_json(self.distribution, &self.parameters),
+        );
+        object.insert("fit".to_owned(), json!(self.fit));
+        object.insert("bootstrap".to_owned(), json!(self.bootstrap));
+        object.insert("seed".to_owned(), json!(self.seed));
+        object.insert(
+            "data".to_owned(),
+            json!(self.data.as_ref().map(|path| path.display().to_string())),
+        );
+        let target = match self.target {
+            Some(Target::StandardError(target)) => json!({ "standard_error": target }),
+            Some(Target::Width(target)) => json!({ "ci_width": target }),
+            None => Value::Null,
+        };
+        object.insert("target".to_owned(), target);
+        object
+    }
+}
+
+/// The JSON object describing the distribution of the `family` with the `parameters`.
+pub fn distribution_json(family: &str, parameters: &[(&str, f64)]) -> Value {
+    let mut distribution = Map::new();
+    distribution.insert("family".to_owned(), json!(family));
+    for &(name, value) in parameters {
+        distribution.insert(name.to_owned(), json!(value));
+    }
+    Value::Object(distribution)
+}
+
+/// The result of a simulation.
+pub enum Outcome {
+    /// The probability that the statistic is in the tail of the given one.
+    PValue {
+        statistic: f64,
+        tail: Tail,
+        pvalue: Estimate,
+        /// Whether the target precision was reached, if the simulation was adaptive.
+        target_reached: Option<bool>,
+    },
+    /// The simulated statistics.
+    Distribution(Vec<f64>),
+    /// The orders and the values of the empirical quantiles of the simulated statistics.
+    Quantiles(Vec<(f64, f64)>),
+    /// The descriptive statistics and the histogram of the simulated statistics.
+    Description {
+        summary: Option<Summary>,
+        histogram: Option<Histogram>,
+        /// Whether the histogram is drawn as a bar chart in the text output.
+        bars: bool,
+    },
+}
+
+/// The names and the values of the descriptive statistics in `summary`.
+fn summary_rows(summary: &Summary) -> Vec<(String, f64)> {
+    let moments = [
+        ("mean", summary.mean),
+        ("variance", summary.variance),
+        ("skewness", summary.skewness),
+        ("kurtosis", summary.kurtosis),
+        ("min", summary.min),
+        ("max", summary.max),
+    ];
+    moments
+        .into_iter()
+        .map(|(name, value)| (name.to_owned(), value))
+        .chain(
+            summary
+                .quantiles
+                .iter()
+                .map(|&(q, value)| (format!("quantile {q}"), value)),
+        )
+        .collect()
+}
+
+/// The bar of `count` in a bar chart whose longest bar is `max_count`.
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_precision_loss,
+    clippy::cast_sign_loss
+)]
+fn bar(count: usize, max_count: usize) -> String {
+    let length = (count as f64 / max_count as f64 * BAR_WIDTH as f64).round() as usize;
+    "#".repeat(length)
+}
+
+/// Joins the numbers by `separator`.
+fn join(values: &[f64], separator: &str) -> String {
+    values
+        .iter()
+        .map(f64::to_string)
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+/// Writes the `estimate` of the probability called `name`, its standard error and its confidence
+/// interval as human-readable text.
+pub fn write_estimate_text(
+    out: &mut impl Write,
+    name: &str,
+    estimate: &Estimate,
+) -> io::Result<()> {
+    writeln!(out, "{name} = {}", estimate.probability)?;
+    writeln!(out, "standard error = {}", estimate.standard_error)?;
+    writeln!(
+        out,
+        "{}% confidence interval ({}) = [{}, {}]",
+        estimate.confidence * 100.0,
+        estimate.method.name(),
+        estimate.lower,
+        estimate.upper
+    )
+}
+
+/// Inserts the `estimate` into the JSON `object`, with the number of iterations in which the event
+/// happened under the key `count` and the probability under the key `probability`, followed by
+/// its standard error and its confidence interval.
+pub fn insert_estimate(
+    object: &mut Map<String, Value>,
+    count: &str,
+    probability: &str,
+    estimate: &Estimate,
+) {
+    object.insert(count.to_owned(), json!(estimate.count));
+    object.insert(probability.to_owned(), json!(estimate.probability));
+    object.insert("standard_error".to_owned(), json!(estimate.standard_error));
+    object.insert(
+        "confidence_interval".to_owned(),
+        json!({
+            "level": estimate.confidence,
+            "method": estimate.method.name(),
+            "lower": estimate.lower,
+            "upper": estimate.upper,
+        }),
+    );
+}
+
+/// Inserts the tested statistic and its p-value into the JSON `object`.
+pub fn insert_pvalue(
+    object: &mut Map<String, Value>,
+    statistic: f64,
+    tail: Tail,
+    pvalue: &Estimate,
+) {
+    object.insert("statistic".to_owned(), json!(statistic));
+    object.insert("tail".to_owned(), json!(tail.name()));
+    insert_estimate(object, "count", "pvalue", pvalue);
+}
+
+/// Writes the tested statistic and its p-value as a table with a header row.
+pub fn write_pvalue_table(
+    out: &mut impl Write,
+    separator: &str,
+    statistic: f64,
+    tail: Tail,
+    iterations: usize,
+    pvalue: &Estimate,
+) -> io::Result<()> {
+    let header = [
+        "statistic",
+        "tail",
+        "iterations",
+        "pvalue",
+        "standard_error",
+        "confidence",
+        "lower",
+        "upper",
+    ];
+    writeln!(out, "{}", header.join(separator))?;
+    let row = [
+        pvalue.probability,
+        pvalue.standard_error,
+        pvalue.confidence,
+        pvalue.lower,
+        pvalue.upper,
+    ];
+    writeln!(
+        out,
+        "{statistic}{separator}{}{separator}{iterations}{separator}{}",
+        tail.name(),
+        join(&row, separator)
+    )
+}
+
+/// The p-value of a test of given datasets, reported by the subcommands that compare datasets.
+pub struct PValueReport {
+    /// The fields of the JSON object that describe the test.
+    pub description: Map<String, Value>,
+    /// The number of iterations of the simulation.
+    pub iterations: usize,
+    /// The statistic of the datasets.
+    pub statistic: f64,
+    /// Whether the statistic is written in the text output.
+    pub show_statistic: bool,
+    /// The tail of the distribution of the statistic measured by the p-value.
+    pub tail: Tail,
+    /// The p-value.
+    pub pvalue: Estimate,
+}
+
+impl PValueReport {
+    /// Prints the report in `format` to the standard output.
+    pub fn print(&self, format: Format) -> io::Result<()> {
+        let mut out = io::stdout().lock();
+        match format {
+            Format::Text => {
+                if self.show_statistic {
+                    writeln!(out, "statistic = {}", self.statistic)?;
+                }
+                write_estimate_text(&mut out, "pvalue", &self.pvalue)
+            }
+            Format::Json => {
+                let mut object = self.description.clone();
+                insert_pvalue(&mut object, self.statistic, self.tail, &self.pvalue);
+                serde_json::to_writer_pretty(&mut out, &Value::Object(object))?;
+                writeln!(out)
+            }
+            Format::Csv | Format::Tsv => write_pvalue_table(
+                &mut out,
+                format.separator().unwrap_or_default(),
+                self.statistic,
+                self.tail,
+                self.iterations,
+                &self.pvalue,
+            ),
+        }
+    }
+}
+
+/// The JSON object with the description of the simulation and its result.
+pub fn to_json(metadata: &Metadata, outcome: &Outcome) -> Value {
+    let mut object = metadata.to_json();
+    match outcome {
+        Outcome::PValue {
+            statistic,
+            tail,
+            pvalue,
+            target_reached,
+        } => {
+            if let Some(reached) = target_reached {
+                object.insert("target_reached".to_owned(), json!(reached));
+            }
+            insert_pvalue(&mut object, *statistic, *tail, pvalue);
+        }
+        Outcome::Distribution(distr) => {
+            object.insert("statistics".to_owned(), json!(distr));
+        }
+        Outcome::Quantiles(quantiles) => {
+            let quantiles: Vec<Value> = quantiles
+                .iter()
+                .map(|(q, value)| json!({ "order": q, "value": value }))
+                .collect();
+            object.insert("quantiles".to_owned(), Value::Array(quantiles));
+        }
+        Outcome::Description {
+            summary, histogram, ..
+        } => {
+            if let Some(summary) = summary {
+                let quantiles: Vec<Value> = summary
+                    .quantiles
+                    .iter()
+                    .map(|(q, value)| json!({ "order": q, "value": value }))
+                    .collect();
+                object.insert(
+                    "summary".to_owned(),
+                    json!({
+                        "mean": summary.mean,
+                        "variance": summary.variance,
+                        "skewness": summary.skewness,
+                        "kurtosis": summary.kurtosis,
+                        "min": summary.min,
+                        "max": summary.max,
+                        "quantiles": quantiles,
+                    }),
+                );
+            }
+            if let Some(histogram) = histogram {
+                let bins: Vec<Value> = histogram
+                    .bins
+                    .iter()
+                    .map(
+                        |bin| json!({ "lower": bin.lower, "upper": bin.upper, "count": bin.count }),
+                    )
+                    .collect();
+                object.insert(
+                    "histogram".to_owned(),
+                    json!({ "bins": bins, "non_finite": histogram.non_finite }),
+                );
+            }
+        }
+    }
+    Value::Object(object)
+}
+
+/// Writes the summary and the histogram of the simulated statistics as human-readable text.
+fn write_description_text(
+    out: &mut impl Write,
+    summary: Option<&Summary>,
+    histogram: Option<&Histogram>,
+    bars: bool,
+) -> io::Result<()> {
+    if let Some(summary) = summary {
+        for (name, value) in summary_rows(summary) {
+            writeln!(out, "{name} = {value}")?;
+        }
+    }
+    if let Some(histogram) = histogram {
+        if summary.is_some() {
+            writeln!(out)?;
+        }
+        let max_count = histogram.bins.iter().map(|bin| bin.count).max();
+        for (i, bin) in histogram.bins.iter().enumerate() {
+            let close = if i + 1 == histogram.bins.len() {
+                ']'
+            } else {
+                ')'
+            };
+            write!(out, "[{}, {}{close} {}", bin.lower, bin.upper, bin.count)?;
+            match max_count {
+                Some(max_count) if bars && max_count > 0 => {
+                    writeln!(out, " {}", bar(bin.count, max_count))?;
+                }
+                _ => writeln!(out)?,
+            }
+        }
+        if histogram.non_finite > 0 {
+            writeln!(out, "non-finite {}", histogram.non_finite)?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes `outcome` of the simulation described by `metadata` as human-readable text.
+fn write_text(out: &mut impl Write, metadata: &Metadata, outcome: &Outcome) -> io::Result<()> {
+    match outcome {
+        Outcome::PValue {
+            statistic,
+            pvalue,
+            target_reached,
+            ..
+        } => {
+            if metadata.data.is_some() {
+                writeln!(out, "statistic = {statistic}")?;
+            }
+            if metadata.bootstrap {
+                for (name, value) in &metadata.parameters {
+                    writeln!(out, "fitted {name} = {value}")?;
+                }
+            }
+            if metadata.target.is_some() {
+                writeln!(out, "iterations = {}", metadata.iterations)?;
+            }
+            if let Some(reached) = target_reached {
+                writeln!(out, "target reached = {reached}")?;
+            }
+            write_estimate_text(out, "pvalue", pvalue)
+        }
+        Outcome::Distribution(distr) => writeln!(out, "{distr:?}"),
+        Outcome::Quantiles(quantiles) => quantiles
+            .iter()
+            .try_for_each(|(q, value)| writeln!(out, "quantile {q} = {value}")),
+        Outcome::Description {
+            summary,
+            histogram,
+            bars,
+        } => write_description_text(out, summary.as_ref(), histogram.as_ref(), *bars),
+    }
+}
+
+/// Writes the summary and the histogram of the simulated statistics as tables with header rows,
+/// separated by an empty line.
+fn write_description_table(
+    out: &mut impl Write,
+    separator: &str,
+    summary: Option<&Summary>,
+    histogram: Option<&Histogram>,
+) -> io::Result<()> {
+    if let Some(summary) = summary {
+        writeln!(out, "name{separator}value")?;
+        for (name, value) in summary_rows(summary) {
+            writeln!(out, "{name}{separator}{value}")?;
+        }
+    }
+    if let Some(histogram) = histogram {
+        if summary.is_some() {
+            writeln!(out)?;
+        }
+        writeln!(out, "lower{separator}upper{separator}count")?;
+        for bin in &histogram.bins {
+            writeln!(
+                out,
+                "{}{separator}{}{separator}{}",
+                bin.lower, bin.upper, bin.count
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes `outcome` of the simulation described by `metadata` as a table with a header row and
+/// columns separated by `separator`.
+fn write_table(
+    out: &mut impl Write,
+    separator: &str,
+    metadata: &Metadata,
+    outcome: &Outcome,
+) -> io::Result<()> {
+    match outcome {
+        Outcome::PValue {
+            statistic,
+            tail,
+            pvalue,
+            ..
+        } => write_pvalue_table(
+            out,
+            separator,
+            *statistic,
+            *tail,
+            metadata.iterations,
+            pvalue,
+        ),
+        Outcome::Distribution(distr) => {
+            writeln!(out, "statistic")?;
+            distr.iter().try_for_each(|x| writeln!(out, "{x}"))
+        }
+        Outcome::Quantiles(quantiles) => {
+            writeln!(out, "order{separator}quantile")?;
+            quantiles
+                .iter()
+                .try_for_each(|(q, value)| writeln!(out, "{q}{separator}{value}"))
+        }
+        Outcome::Description {
+            summary, histogram, ..
+        } => write_description_table(out, separator, summary.as_ref(), histogram.as_ref()),
+    }
+}
+
+/// Prints `outcome` of the simulation described by `metadata` to the standard output.
+pub fn print(format: Format, metadata: &Metadata, outcome: &Outcome) -> io::Result<()> {
+    let mut out = io::stdout().lock();
+    match format {
+        Format::Text => write_text(&mut out, metadata, outcome),
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut out, &to_json(metadata, outcome))?;
+            writeln!(out)
+        }
+        Format::Csv | Format::Tsv => {
+            let separator = format.separator().unwrap_or_default();
+            write_table(&mut out, separator, metadata, outcome)
+        }
+    }
+}
@@ -0,0 +1,110 @@
+// Copyright 2024 Vladimir Kharchev
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The `permutation` subcommand, which runs a permutation test on two groups of labelled values.
+
+use std::path::PathBuf;
+
+use clap::Args;
+use monty_carlos::sample::Sample;
+use serde_json::{json, Map, Value};
+
+use crate::data;
+use crate::error::CliError;
+use crate::estimate::{EstimateArg, Tail};
+use crate::gof;
+use crate::output::{Format, PValueReport};
+use crate::sample::PermutationSample;
+use crate::simulation::{RunArg, Simulation};
+use crate::statistic::TwoSampleStatistic;
+
+/// The command-line arguments of the `permutation` subcommand.
+#[derive(Args, Debug)]
+pub struct PermutationArg {
+    /// The CSV file of the labelled values (`-` for the standard input).
+    ///
+    /// Every row consists of a group label and a value. The file must contain exactly two groups,
+    /// and the first group is the one whose label appears first.
+    #[arg(long, value_name = "PATH")]
+    data: PathBuf,
+    /// The tail of the permutation distribution measured by the p-value.
+    ///
+    /// The default is the upper tail for the Kolmogorov-Smirnov distance, and both tails for the
+    /// differences.
+    #[arg(long, value_enum)]
+    tail: Option<Tail>,
+    /// Number of random permutations.
+    #[arg(long)]
+    iterations: Option<usize>,
+    /// The seed and the number of threads of the simulation.
+    #[command(flatten)]
+    run: RunArg,
+    /// How the p-value is estimated.
+    #[command(flatten)]
+    estimate: EstimateArg,
+    /// The format of the output.
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+    /// The statistic comparing the groups.
+    #[arg(value_enum)]
+    statistic: TwoSampleStatistic,
+}
+
+/// Runs the permutation test described by `args` and prints the p-value.
+///
+/// The group labels are shuffled by shuffling the pooled values, and the statistic of the observed
+/// groups is compared with the statistics of the random permutations.
+pub fn run(args: &PermutationArg) -> Result<(), CliError> {
+    let [(label_a, mut a), (label_b, mut b)] = data::read_groups(&args.data)?;
+    a.sort_unstable_by(f64::total_cmp);
+    b.sort_unstable_by(f64::total_cmp);
+    let statistic = args.statistic.evaluate(&a, &b);
+    let tail = args.tail.unwrap_or(match args.statistic {
+        TwoSampleStatistic::KolmogorovSmirnov => Tail::Upper,
+        TwoSampleStatistic::MeanDifference | TwoSampleStatistic::MedianDifference => Tail::TwoSided,
+    });
+    let seed = args.run.seed();
+    let simulator = Simulation::new(
+        || -> Box<dyn Sample> {
+            Box::new(
+                PermutationSample::new(&a, &b, args.statistic).expect("the groups are not empty"),
+            )
+        },
+        seed,
+        args.iterations,
+        &args.run,
+    );
+    gof::check_iterations(simulator.iterations)?;
+    let counts = simulator.count(statistic);
+    let groups: Vec<Value> = [(label_a, a.len()), (label_b, b.len())]
+        .iter()
+        .map(|(label, samples)| json!({ "label": label, "samples": samples }))
+        .collect();
+    let mut description = Map::new();
+    description.insert("test".to_owned(), json!("permutation"));
+    description.insert("measure".to_owned(), json!(args.statistic.name()));
+    description.insert("groups".to_owned(), Value::Array(groups));
+    description.insert("iterations".to_owned(), json!(simulator.iterations));
+    description.insert("seed".to_owned(), json!(seed));
+    description.insert("data".to_owned(), json!(args.data.display().to_string()));
+    let report = PValueReport {
+        description,
+        iterations: simulator.iterations,
+        statistic,
+        show_statistic: true,
+        tail,
+        pvalue: args.estimate.estimate(tail, &counts),
+    };
+    Ok(report.print(args.format)?)
+}
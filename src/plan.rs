@@ -0,0 +1,175 @@
+// Copyright 2024 Vladimir Kharchev
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The `plan` subcommand, which finds the smallest sample size at which a test reaches a target
+//! power against an alternative.
+//!
+//! The sample size is doubled from the minimal one until the simulated power reaches the target,
+//! and then the smallest sample size is found by bisection between the last two sizes. The power
+//! is assumed to grow with the sample size, and every size is simulated with the same seed, which
+//! keeps the simulated powers close to monotonic. Every simulated size belongs to the reported
+//! power curve; the sizes below the found one don't reach the target, and the others do.
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+use clap::Args;
+use serde_json::{json, Map, Value};
+
+use crate::error::CliError;
+use crate::estimate;
+use crate::gof::{ModelArg, Test};
+use crate::output::Format;
+use crate::power::{AlternativeArg, Power, Study};
+use crate::simulation::RunArg;
+
+/// The command-line arguments of the `plan` subcommand.
+#[derive(Args, Debug)]
+pub struct PlanArg {
+    /// The power that the test has to reach.
+    #[arg(
+        long,
+        default_value = "0.8",
+        value_parser = |value: &str| estimate::parse_probability(value, "the target power")
+    )]
+    power: f64,
+    /// The smallest sample size that is considered.
+    #[arg(long, default_value = "2")]
+    min_samples: usize,
+    /// The largest sample size that is considered.
+    #[arg(long, default_value = "10000")]
+    max_samples: usize,
+    /// The alternative, the significance level and the precision of the simulations.
+    #[command(flatten)]
+    alternative: AlternativeArg,
+    /// The seed, the number of threads and the progress of the simulations.
+    #[command(flatten)]
+    run: RunArg,
+    /// The format of the output.
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+    /// The null hypothesis of the test.
+    #[command(flatten, next_help_heading = "Null distribution")]
+    model: ModelArg,
+    /// The goodness-of-fit test whose sample size is planned.
+    #[arg(value_enum)]
+    test: Test,
+}
+
+/// The result of the search for the smallest sample size.
+struct Plan {
+    /// The simulated powers by sample size.
+    curve: BTreeMap<usize, Power>,
+    /// The smallest sample size that reaches the target, or `None` if even the largest one
+    /// doesn't.
+    samples: Option<usize>,
+}
+
+/// Searches for the smallest sample size between `min` and `max` at which `study` reaches the
+/// `target` power.
+fn search(study: &Study, target: f64, min: usize, max: usize) -> Result<Plan, CliError> {
+    let mut curve = BTreeMap::new();
+    let mut reaches = |samples: usize| -> Result<bool, CliError> {
+        let power = study.power(samples)?;
+        let reached = power.power.probability >= target;
+        curve.insert(samples, power);
+        Ok(reached)
+    };
+    // The largest size that doesn't reach the target and the smallest one that does
+    let (mut lower, mut upper) = (None, min);
+    while !reaches(upper)? {
+        if upper == max {
+            return Ok(Plan {
+                curve,
+                samples: None,
+            });
+        }
+        lower = Some(upper);
+        upper = upper.saturating_mul(2).min(max);
+    }
+    if let Some(mut lower) = lower {
+        while upper - lower > 1 {
+            let middle = lower + (upper - lower) / 2;
+            if reaches(middle)? {
+                upper = middle;
+            } else {
+                lower = middle;
+            }
+        }
+    }
+    Ok(Plan {
+        curve,
+        samples: Some(upper),
+    })
+}
+
+/// Finds the sample size described by `args` and prints it together with the power curve.
+pub fn run(args: &PlanArg) -> Result<(), CliError> {
+    if args.min_samples > args.max_samples {
+        return Err(CliError::Parameter(format!(
+            "--min-samples {} is greater than --max-samples {}",
+            args.min_samples, args.max_samples
+        )));
+    }
+    let study = Study::new(args.model, args.test, args.alternative, args.run)?;
+    let plan = search(&study, args.power, args.min_samples, args.max_samples)?;
+    Ok(print(args, &study, &plan)?)
+}
+
+/// Prints the `plan` of the `study` described by `args` to the standard output.
+fn print(args: &PlanArg, study: &Study, plan: &Plan) -> io::Result<()> {
+    let mut out = io::stdout().lock();
+    match args.format {
+        Format::Text => {
+            for power in plan.curve.values() {
+                let estimate = &power.power;
+                writeln!(
+                    out,
+                    "power at {} = {} [{}, {}]",
+                    power.samples, estimate.probability, estimate.lower, estimate.upper
+                )?;
+            }
+            match plan.samples {
+                Some(samples) => writeln!(out, "smallest sample size = {samples}"),
+                None => writeln!(
+                    out,
+                    "the power {} is not reached up to the sample size {}",
+                    args.power, args.max_samples
+                ),
+            }
+        }
+        Format::Json => {
+            let mut object = Map::new();
+            study.insert_json(&mut object);
+            object.insert("target_power".to_owned(), json!(args.power));
+            object.insert("samples".to_owned(), json!(plan.samples));
+            let curve = plan
+                .curve
+                .values()
+                .map(|power| Value::Object(power.to_json()))
+                .collect();
+            object.insert("curve".to_owned(), Value::Array(curve));
+            serde_json::to_writer_pretty(&mut out, &Value::Object(object))?;
+            writeln!(out)
+        }
+        Format::Csv | Format::Tsv => {
+            let separator = args.format.separator().unwrap_or_default();
+            writeln!(out, "{}", Power::HEADER.join(separator))?;
+            for power in plan.curve.values() {
+                writeln!(out, "{}", power.row().join(separator))?;
+            }
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,286 @@
+// Copyright 2024 Vladimir Kharchev
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The `power` subcommand, which simulates the power of a test against an alternative.
+//!
+//! The critical value of the test at the significance level is simulated under the null
+//! hypothesis first, like in the `table` subcommand. Then datasets are drawn from the alternative
+//! distribution, and the power is the proportion of them whose statistic exceeds the critical
+//! value.
+
+use std::io::{self, Write};
+
+use clap::Args;
+use serde_json::{json, Map, Value};
+
+use crate::distribution::{DistributionArg, Null};
+use crate::empirical;
+use crate::error::CliError;
+use crate::estimate::{self, Counts, Estimate, IntervalMethod};
+use crate::fitting::FitFamily;
+use crate::gof::{self, ModelArg, Setup, Test};
+use crate::output::{self, Format};
+use crate::simulation::{RunArg, Simulation};
+use crate::sweep::{self, Sweep};
+
+/// The command-line arguments describing the alternative and the precision of the power.
+#[derive(Args, Clone, Copy, Debug)]
+pub struct AlternativeArg {
+    /// The distribution from which the datasets are drawn under the alternative hypothesis.
+    ///
+    /// It is written as `FAMILY[:PARAMETER=VALUE,...]`, where the parameters are named like the
+    /// options of the null distribution, for example `students-t:freedom=5`.
+    #[arg(long, value_name = "DISTRIBUTION", value_parser = DistributionArg::from_spec)]
+    pub alternative: DistributionArg,
+    /// The significance level of the test.
+    #[arg(
+        long,
+        default_value = "0.05",
+        value_parser = |value: &str| estimate::parse_probability(value, "the significance level")
+    )]
+    pub alpha: f64,
+    /// Number of iterations of the simulation under the null hypothesis and of the one under the
+    /// alternative.
+    #[arg(long)]
+    pub iterations: Option<usize>,
+    /// The confidence level of the interval for the power.
+    #[arg(
+        long,
+        default_value = "0.95",
+        value_parser = |value: &str| estimate::parse_probability(value, "the confidence level")
+    )]
+    pub confidence: f64,
+    /// The method of the confidence interval for the power.
+    #[arg(long, value_enum, default_value_t = IntervalMethod::Wilson)]
+    pub interval: IntervalMethod,
+}
+
+/// The command-line arguments of the `power` subcommand.
+#[derive(Args, Debug)]
+pub struct PowerArg {
+    /// The alternative, the significance level and the precision of the simulations.
+    #[command(flatten)]
+    alternative: AlternativeArg,
+    /// Simulate the power for a range of values of a parameter of the alternative.
+    ///
+    /// It is written as `PARAMETER=FROM:TO:STEP`, for example `mean=0:1:0.1`. The critical value
+    /// is simulated once and every alternative is simulated with the same seed.
+    #[arg(long, value_name = "RANGE", value_parser = sweep::parse_sweep)]
+    sweep: Option<Sweep>,
+    /// The seed, the number of threads and the progress of the simulations.
+    #[command(flatten)]
+    run: RunArg,
+    /// The format of the output.
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+    /// The null hypothesis of the test.
+    #[command(flatten, next_help_heading = "Null distribution")]
+    model: ModelArg,
+    /// Size of the datasets.
+    samples: usize,
+    /// The goodness-of-fit test whose power is simulated.
+    #[arg(value_enum)]
+    test: Test,
+}
+
+/// A test together with the alternative against which its power is simulated.
+pub struct Study {
+    /// The test and its null hypothesis.
+    pub setup: Setup,
+    /// The distribution from which the datasets are drawn under the alternative.
+    pub alternative: Null,
+    /// The alternative, the significance level and the precision of the simulations.
+    pub args: AlternativeArg,
+    /// The seed of every simulation.
+    pub seed: u64,
+    /// The number of threads and the progress of the simulations.
+    pub run: RunArg,
+}
+
+/// The simulated power of a test at a sample size.
+pub struct Power {
+    /// The size of the datasets.
+    pub samples: usize,
+    /// The critical value of the statistic.
+    pub critical_value: f64,
+    /// The proportion of the datasets drawn from the alternative that are rejected.
+    pub power: Estimate,
+}
+
+impl Study {
+    /// Constructs the study of `test` described by the command-line arguments.
+    pub fn new(
+        model: ModelArg,
+        test: Test,
+        args: AlternativeArg,
+        run: RunArg,
+    ) -> Result<Self, CliError> {
+        Ok(Self {
+            setup: model.setup(test)?,
+            alternative: args.alternative.build().map_err(CliError::Parameter)?,
+            args,
+            seed: run.seed(),
+            run,
+        })
+    }
+
+    /// Simulates the power of the test for datasets of size `samples`.
+    ///
+    /// Both simulations use the seed of the study.
+    pub fn power(&self, samples: usize) -> Result<Power, CliError> {
+        let (critical_value, iterations) = self.critical_value(samples)?;
+        self.power_against(self.alternative, samples, critical_value, iterations)
+    }
+
+    /// Simulates the critical value of the test for datasets of size `samples` and returns it
+    /// together with the number of iterations of the simulation.
+    pub fn critical_value(&self, samples: usize) -> Result<(f64, usize), CliError> {
+        let null = Simulation::new(
+            self.setup.sampler(samples)?,
+            self.seed,
+            self.args.iterations,
+            &self.run,
+        );
+        gof::check_iterations(null.iterations)?;
+        let mut distribution = null.simulate_distribution();
+        distribution.sort_unstable_by(f64::total_cmp);
+        let critical_value = empirical::quantile(&distribution, 1.0 - self.args.alpha);
+        Ok((critical_value, null.iterations))
+    }
+
+    /// Simulates the proportion of `iterations` datasets of size `samples` drawn from
+    /// `alternative` whose statistic exceeds `critical_value`.
+    pub fn power_against(
+        &self,
+        alternative: Null,
+        samples: usize,
+        critical_value: f64,
+        iterations: usize,
+    ) -> Result<Power, CliError> {
+        let simulator = Simulation::new(
+            self.setup.alternative_sampler(alternative, samples)?,
+            self.seed,
+            Some(iterations),
+            &self.run,
+        );
+        let Counts { greater, .. } = simulator.count(critical_value);
+        Ok(Power {
+            samples,
+            critical_value,
+            power: Estimate::new(
+                greater,
+                simulator.iterations,
+                self.args.confidence,
+                self.args.interval,
+            ),
+        })
+    }
+
+    /// Inserts the description of the study into the JSON `object`.
+    pub fn insert_json(&self, object: &mut Map<String, Value>) {
+        let null = &self.setup.distribution;
+        let alternative = &self.args.alternative;
+        object.insert("test".to_owned(), json!(self.setup.test.name()));
+        object.insert(
+            "distribution".to_owned(),
+            output::distribution_json(null.family().name(), &null.parameters()),
+        );
+        object.insert("fit".to_owned(), json!(self.setup.fit.map(FitFamily::name)));
+        object.insert(
+            "alternative".to_owned(),
+            output::distribution_json(alternative.family().name(), &alternative.parameters()),
+        );
+        object.insert("alpha".to_owned(), json!(self.args.alpha));
+        object.insert("seed".to_owned(), json!(self.seed));
+    }
+}
+
+impl Power {
+    /// The names of the columns of [`Power::row`].
+    pub const HEADER: [&'static str; 8] = [
+        "samples",
+        "iterations",
+        "critical_value",
+        "power",
+        "standard_error",
+        "confidence",
+        "lower",
+        "upper",
+    ];
+
+    /// The fields of the power as a row of a table.
+    pub fn row(&self) -> [String; 8] {
+        let power = &self.power;
+        [
+            self.samples.to_string(),
+            power.iterations.to_string(),
+            self.critical_value.to_string(),
+            power.probability.to_string(),
+            power.standard_error.to_string(),
+            power.confidence.to_string(),
+            power.lower.to_string(),
+            power.upper.to_string(),
+        ]
+    }
+
+    /// The JSON object with the fields of the power.
+    pub fn to_json(&self) -> Map<String, Value> {
+        let power = &self.power;
+        let mut object = Map::new();
+        object.insert("samples".to_owned(), json!(self.samples));
+        object.insert("iterations".to_owned(), json!(power.iterations));
+        object.insert("critical_value".to_owned(), json!(self.critical_value));
+        output::insert_estimate(&mut object, "rejections", "power", power);
+        object
+    }
+}
+
+/// Simulates the power described by `args` and prints it.
+pub fn run(args: &PowerArg) -> Result<(), CliError> {
+    let mut alternative = args.alternative;
+    if let Some(sweep) = &args.sweep {
+        // The swept parameter may be required by the family, so it is set before the study is
+        // constructed
+        sweep.apply(&mut alternative.alternative, sweep.values[0])?;
+    }
+    let study = Study::new(args.model, args.test, alternative, args.run)?;
+    if let Some(sweep) = &args.sweep {
+        return sweep::run(&study, sweep, args.samples, args.format);
+    }
+    let power = study.power(args.samples)?;
+    Ok(print(args, &study, &power)?)
+}
+
+/// Prints the `power` of the `study` described by `args` to the standard output.
+fn print(args: &PowerArg, study: &Study, power: &Power) -> io::Result<()> {
+    let mut out = io::stdout().lock();
+    match args.format {
+        Format::Text => {
+            writeln!(out, "critical value = {}", power.critical_value)?;
+            output::write_estimate_text(&mut out, "power", &power.power)
+        }
+        Format::Json => {
+            let mut object = Map::new();
+            study.insert_json(&mut object);
+            object.extend(power.to_json());
+            serde_json::to_writer_pretty(&mut out, &Value::Object(object))?;
+            writeln!(out)
+        }
+        Format::Csv | Format::Tsv => {
+            let separator = args.format.separator().unwrap_or_default();
+            writeln!(out, "{}", Power::HEADER.join(separator))?;
+            writeln!(out, "{}", power.row().join(separator))
+        }
+    }
+}
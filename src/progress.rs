@@ -0,0 +1,194 @@
+// Copyright 2024 Vladimir Kharchev
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Reporting the progress of long simulations on the standard error.
+//!
+//! If the standard error is a terminal, the progress is a bar that is redrawn in place. Otherwise,
+//! for example in a log file, a line is written periodically. Nothing is reported for simulations
+//! that finish before the first report is due.
+
+use std::io::{self, IsTerminal};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The number of characters in the progress bar.
+const BAR_WIDTH: usize = 30;
+
+/// How the progress is reported.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Style {
+    /// A progress bar redrawn in place.
+    Bar,
+    /// Periodic log lines.
+    Log,
+}
+
+impl Style {
+    /// The time between the reports, which is also the time before the first one.
+    fn interval(self) -> Duration {
+        match self {
+            Style::Bar => Duration::from_millis(200),
+            Style::Log => Duration::from_secs(10),
+        }
+    }
+}
+
+/// The mutable part of [`Progress`].
+#[derive(Debug)]
+struct State {
+    /// The number of iterations done so far.
+    done: usize,
+    /// The time since the start at which the next report is due.
+    next_report: Duration,
+    /// Whether anything has been reported.
+    reported: bool,
+}
+
+/// The progress of a simulation.
+#[derive(Debug)]
+pub struct Progress {
+    /// The total number of iterations, or `None` if it is not known in advance.
+    total: Option<usize>,
+    start: Instant,
+    /// `None` if the progress is not reported.
+    style: Option<Style>,
+    state: Mutex<State>,
+}
+
+impl Progress {
+    /// Starts tracking the progress of `total` iterations.
+    ///
+    /// Nothing is reported if `quiet` is `true`.
+    pub fn new(total: usize, quiet: bool) -> Self {
+        Self::with_total(Some(total), quiet)
+    }
+
+    /// Starts tracking the progress of a simulation whose number of iterations is not known in
+    /// advance, which is reported without the percentage and the remaining time.
+    ///
+    /// Nothing is reported if `quiet` is `true`.
+    pub fn unbounded(quiet: bool) -> Self {
+        Self::with_total(None, quiet)
+    }
+
+    /// Starts tracking the progress of `total` iterations, if it is known.
+    fn with_total(total: Option<usize>, quiet: bool) -> Self {
+        let style = (!quiet && total != Some(0)).then(|| {
+            if io::stderr().is_terminal() {
+                Style::Bar
+            } else {
+                Style::Log
+            }
+        });
+        Self {
+            total,
+            start: Instant::now(),
+            style,
+            state: Mutex::new(State {
+                done: 0,
+                next_report: style.map_or(Duration::ZERO, Style::interval),
+                reported: false,
+            }),
+        }
+    }
+
+    /// Records that `iterations` more iterations are done, and reports the progress if a report is
+    /// due.
+    pub fn advance(&self, iterations: usize) {
+        let Some(style) = self.style else {
+            return;
+        };
+        let mut state = self.state.lock().unwrap();
+        state.done += iterations;
+        let elapsed = self.start.elapsed();
+        if elapsed >= state.next_report {
+            state.next_report = elapsed + style.interval();
+            state.reported = true;
+            self.report(style, state.done, elapsed);
+        }
+    }
+
+    /// Finishes the report, if anything was reported.
+    pub fn finish(&self) {
+        let Some(style) = self.style else {
+            return;
+        };
+        let state = self.state.lock().unwrap();
+        if !state.reported {
+            return;
+        }
+        let elapsed = self.start.elapsed();
+        match style {
+            Style::Bar => {
+                self.report(style, state.done, elapsed);
+                eprintln!();
+            }
+            Style::Log => eprintln!(
+                "progress: {} iterations done in {}",
+                state.done,
+                format_duration(elapsed.as_secs_f64())
+            ),
+        }
+    }
+
+    /// Reports that `done` iterations are done in the time `elapsed`.
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss
+    )]
+    fn report(&self, style: Style, done: usize, elapsed: Duration) {
+        let rate = done as f64 / elapsed.as_secs_f64();
+        let Some(total) = self.total else {
+            match style {
+                Style::Bar => eprint!("\r{done} iterations {rate:.0} it/s  "),
+                Style::Log => eprintln!("progress: {done} iterations, {rate:.0} it/s"),
+            }
+            return;
+        };
+        let fraction = done as f64 / total as f64;
+        let eta = format_duration(total.saturating_sub(done) as f64 / rate);
+        let percent = 100.0 * fraction;
+        match style {
+            Style::Bar => {
+                let filled = ((fraction * BAR_WIDTH as f64) as usize).min(BAR_WIDTH);
+                eprint!(
+                    "\r[{}{}] {percent:3.0}% {done}/{total} {rate:.0} it/s ETA {eta}  ",
+                    "#".repeat(filled),
+                    " ".repeat(BAR_WIDTH - filled)
+                );
+            }
+            Style::Log => eprintln!(
+                "progress: {percent:.0}% ({done}/{total} iterations), {rate:.0} it/s, ETA {eta}"
+            ),
+        }
+    }
+}
+
+/// Formats a duration of `seconds` for humans, in whole seconds.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn format_duration(seconds: f64) -> String {
+    if !seconds.is_finite() {
+        return "unknown".to_owned();
+    }
+    let seconds = seconds.round() as u64;
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{hours}h{minutes:02}m{seconds:02}s")
+    } else if minutes > 0 {
+        format!("{minutes}m{seconds:02}s")
+    } else {
+        format!("{seconds}s")
+    }
+}
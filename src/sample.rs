@@ -0,0 +1,229 @@
+// Copyright 2024 Vladimir Kharchev
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Implementations of [`Sample`] in addition to the ones provided by [`monty_carlos`].
+
+use monty_carlos::sample::Sample;
+use rand::distributions::{Distribution, Standard};
+use rand::seq::SliceRandom;
+use rand::RngCore;
+use statrs::distribution::ContinuousCDF;
+
+use crate::fitting::Fit;
+use crate::statistic::{self, Statistic, TwoSampleStatistic};
+
+/// Fills `data` with values drawn from `distribution` and sorts it.
+fn draw_sorted<D: Distribution<f64>>(distribution: &D, data: &mut [f64], rng: &mut dyn RngCore) {
+    for x in data.iter_mut() {
+        *x = distribution.sample(rng);
+    }
+    data.sort_unstable_by(f64::total_cmp);
+}
+
+/// A sample of a goodness-of-fit test with a completely specified null distribution.
+///
+/// The sample is drawn from `distribution` and the statistic is calculated against the same
+/// distribution.
+pub struct KnownSample<D> {
+    distribution: D,
+    statistic: Statistic,
+    data: Vec<f64>,
+}
+
+impl<D> KnownSample<D> {
+    /// Creates the sample of size `samples`.
+    ///
+    /// Returns `None` if `samples` is zero.
+    pub fn new(distribution: D, samples: usize, statistic: Statistic) -> Option<Self> {
+        if samples == 0 {
+            return None;
+        }
+        Some(Self {
+            distribution,
+            statistic,
+            data: vec![0.0; samples],
+        })
+    }
+}
+
+impl<D: Distribution<f64> + ContinuousCDF<f64, f64>> Sample for KnownSample<D> {
+    fn generate_sample(&mut self, rng: &mut dyn RngCore) {
+        draw_sorted(&self.distribution, &mut self.data, rng);
+    }
+
+    fn evaluate(&self) -> f64 {
+        self.statistic.evaluate(&self.data, &self.distribution)
+    }
+}
+
+/// A sample of a goodness-of-fit test with a completely specified null distribution under an
+/// alternative.
+///
+/// The sample is drawn from `alternative` and the statistic is calculated against `null`.
+pub struct AlternativeSample<A, D> {
+    alternative: A,
+    null: D,
+    statistic: Statistic,
+    data: Vec<f64>,
+}
+
+impl<A, D> AlternativeSample<A, D> {
+    /// Creates the sample of size `samples`.
+    ///
+    /// Returns `None` if `samples` is zero.
+    pub fn new(alternative: A, null: D, samples: usize, statistic: Statistic) -> Option<Self> {
+        if samples == 0 {
+            return None;
+        }
+        Some(Self {
+            alternative,
+            null,
+            statistic,
+            data: vec![0.0; samples],
+        })
+    }
+}
+
+impl<A: Distribution<f64>, D: ContinuousCDF<f64, f64>> Sample for AlternativeSample<A, D> {
+    fn generate_sample(&mut self, rng: &mut dyn RngCore) {
+        draw_sorted(&self.alternative, &mut self.data, rng);
+    }
+
+    fn evaluate(&self) -> f64 {
+        self.statistic.evaluate(&self.data, &self.null)
+    }
+}
+
+/// A sample of a Lilliefors-style test for an arbitrary fitted family.
+///
+/// The sample is drawn from `distribution`, then a member of the family is fitted to the sample by
+/// `fit` and the statistic is calculated against the fitted distribution.
+pub struct FittedSample<D, F> {
+    distribution: D,
+    fit: F,
+    statistic: Statistic,
+    data: Vec<f64>,
+}
+
+impl<D, F> FittedSample<D, F> {
+    /// Creates the sample of size `samples`.
+    ///
+    /// Returns `None` if `samples` is zero.
+    pub fn new(distribution: D, samples: usize, fit: F, statistic: Statistic) -> Option<Self> {
+        if samples == 0 {
+            return None;
+        }
+        Some(Self {
+            distribution,
+            fit,
+            statistic,
+            data: vec![0.0; samples],
+        })
+    }
+}
+
+impl<D: Distribution<f64>, F: Fit> Sample for FittedSample<D, F> {
+    fn generate_sample(&mut self, rng: &mut dyn RngCore) {
+        draw_sorted(&self.distribution, &mut self.data, rng);
+    }
+
+    /// Evaluates the statistic against the fitted distribution.
+    ///
+    /// A sample that can't be fitted by the family at all gets an infinite statistic.
+    fn evaluate(&self) -> f64 {
+        match self.fit.fit(&self.data) {
+            Some(fitted) => self.statistic.evaluate(&self.data, &fitted),
+            None => f64::INFINITY,
+        }
+    }
+}
+
+/// A sample of the two-sample Kolmogorov-Smirnov test under the null hypothesis.
+///
+/// The statistic doesn't depend on the common continuous distribution of the datasets, so both
+/// datasets are drawn from the standard uniform distribution.
+pub struct TwoSampleKSSample {
+    a: Vec<f64>,
+    b: Vec<f64>,
+}
+
+impl TwoSampleKSSample {
+    /// Creates the sample of datasets of sizes `samples_a` and `samples_b`.
+    ///
+    /// Returns `None` if either size is zero.
+    pub fn new(samples_a: usize, samples_b: usize) -> Option<Self> {
+        if samples_a == 0 || samples_b == 0 {
+            return None;
+        }
+        Some(Self {
+            a: vec![0.0; samples_a],
+            b: vec![0.0; samples_b],
+        })
+    }
+}
+
+impl Sample for TwoSampleKSSample {
+    fn generate_sample(&mut self, rng: &mut dyn RngCore) {
+        draw_sorted(&Standard, &mut self.a, rng);
+        draw_sorted(&Standard, &mut self.b, rng);
+    }
+
+    fn evaluate(&self) -> f64 {
+        statistic::kolmogorov_smirnov_two_sample(&self.a, &self.b)
+    }
+}
+
+/// A sample of the permutation distribution of a two-sample statistic.
+///
+/// The pooled values of two datasets are shuffled, and the first values are assigned to the first
+/// dataset and the rest to the second one, which is the same as shuffling the group labels of the
+/// values.
+pub struct PermutationSample {
+    statistic: TwoSampleStatistic,
+    pooled: Vec<f64>,
+    a: Vec<f64>,
+    b: Vec<f64>,
+}
+
+impl PermutationSample {
+    /// Creates the sample that permutes the values of datasets `a` and `b`.
+    ///
+    /// Returns `None` if either dataset is empty.
+    pub fn new(a: &[f64], b: &[f64], statistic: TwoSampleStatistic) -> Option<Self> {
+        if a.is_empty() || b.is_empty() {
+            return None;
+        }
+        Some(Self {
+            statistic,
+            pooled: a.iter().chain(b).copied().collect(),
+            a: a.to_vec(),
+            b: b.to_vec(),
+        })
+    }
+}
+
+impl Sample for PermutationSample {
+    fn generate_sample(&mut self, rng: &mut dyn RngCore) {
+        self.pooled.shuffle(rng);
+        let (a, b) = self.pooled.split_at(self.a.len());
+        self.a.copy_from_slice(a);
+        self.b.copy_from_slice(b);
+        self.a.sort_unstable_by(f64::total_cmp);
+        self.b.sort_unstable_by(f64::total_cmp);
+    }
+
+    fn evaluate(&self) -> f64 {
+        self.statistic.evaluate(&self.a, &self.b)
+    }
+}
@@ -0,0 +1,292 @@
+// Copyright 2024 Vladimir Kharchev
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Running [`MonteCarlo`] simulations on several threads.
+//!
+//! The iterations of a simulation are split into chunks of [`CHUNK_ITERATIONS`]. Every chunk is
+//! simulated by its own [`MonteCarlo`] with a random number generator seeded by the master seed
+//! and switched to the stream numbered by the chunk. The results of the chunks are combined in the
+//! order of the chunks, so the result depends only on the seed and the number of iterations, and
+//! not on the number of threads or on the order in which the threads finish.
+
+use std::num::NonZeroUsize;
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use clap::Args;
+use monty_carlos::sample::Sample;
+use monty_carlos::MonteCarlo;
+use rand::SeedableRng;
+use rand_chacha::ChaCha12Rng;
+
+use crate::estimate::Counts;
+use crate::progress::Progress;
+
+/// The number of iterations in every chunk except the last one.
+pub const CHUNK_ITERATIONS: usize = 1000;
+
+/// The command-line arguments controlling how a simulation is run.
+#[derive(Args, Clone, Copy, Debug)]
+pub struct RunArg {
+    /// Seed of the random number generator.
+    ///
+    /// Runs with the same seed and the same arguments produce identical results. If the seed is
+    /// not given, a random one is chosen and printed to the standard error.
+    #[arg(long)]
+    pub seed: Option<u64>,
+    /// Number of threads running the simulation.
+    ///
+    /// The result for a given seed doesn't depend on the number of threads.
+    #[arg(long, default_value = "1")]
+    pub threads: NonZeroUsize,
+    /// Don't report the progress of the simulation.
+    ///
+    /// The progress is reported on the standard error as a bar if it is a terminal, and otherwise
+    /// as a line every ten seconds.
+    #[arg(long)]
+    pub quiet: bool,
+}
+
+impl RunArg {
+    /// The seed given on the command line, or a random one, which is printed to the standard
+    /// error.
+    pub fn seed(&self) -> u64 {
+        self.seed.unwrap_or_else(|| {
+            let seed = rand::random();
+            eprintln!("seed = {seed}");
+            seed
+        })
+    }
+}
+
+/// Calls `f` on every index in `indices` on up to `threads` threads and returns the results in
+/// the order of the indices.
+///
+/// The threads take the next index as soon as they finish the previous one, so the order in which
+/// `f` is called is not fixed, but the order of the results is.
+pub fn parallel_map<T, F>(indices: Range<usize>, threads: NonZeroUsize, f: F) -> Vec<T>
+where
+    T: Send,
+    F: Fn(usize) -> T + Sync,
+{
+    let next = AtomicUsize::new(indices.start);
+    let results = Mutex::new(Vec::with_capacity(indices.len()));
+    let worker = || loop {
+        let index = next.fetch_add(1, Ordering::Relaxed);
+        if index >= indices.end {
+            break;
+        }
+        let result = f(index);
+        results.lock().unwrap().push((index, result));
+    };
+    thread::scope(|scope| {
+        for _ in 1..threads.get().min(indices.len()) {
+            scope.spawn(worker);
+        }
+        worker();
+    });
+    let mut results = results.into_inner().unwrap();
+    results.sort_unstable_by_key(|&(index, _)| index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// A Monte-Carlo simulation split into independent chunks.
+pub struct Simulation<F> {
+    /// Creates a new sample for every chunk.
+    make_sample: F,
+    /// The master seed.
+    seed: u64,
+    /// The total number of iterations.
+    pub iterations: usize,
+    /// The number of threads that simulate the chunks.
+    threads: NonZeroUsize,
+    /// Whether the progress is not reported.
+    quiet: bool,
+}
+
+impl<F: Fn() -> Box<dyn Sample> + Sync> Simulation<F> {
+    /// Creates the simulation.
+    ///
+    /// If `iterations` is `None`, the default number of iterations of [`MonteCarlo`] is used. The
+    /// number of threads and the reporting of the progress are taken from `run`.
+    pub fn new(make_sample: F, seed: u64, iterations: Option<usize>, run: &RunArg) -> Self {
+        let iterations = iterations.unwrap_or_else(|| MonteCarlo::new(make_sample()).iterations);
+        Self {
+            make_sample,
+            seed,
+            iterations,
+            threads: run.threads,
+            quiet: run.quiet,
+        }
+    }
+
+    /// The random number generator of the chunk `index`.
+    fn chunk_rng(&self, index: usize) -> ChaCha12Rng {
+        let mut rng = ChaCha12Rng::seed_from_u64(self.seed);
+        rng.set_stream(index as u64);
+        rng
+    }
+
+    /// Starts tracking the progress of the whole simulation.
+    fn progress(&self) -> Progress {
+        Progress::new(self.iterations, self.quiet)
+    }
+
+    /// The number of chunks in the simulation.
+    fn chunks(&self) -> usize {
+        self.iterations.div_ceil(CHUNK_ITERATIONS)
+    }
+
+    /// Runs `job` on a simulator of every chunk in `chunks` and returns the results in the order
+    /// of the chunks, recording every finished chunk in `progress`.
+    fn run<T, J>(&self, chunks: Range<usize>, progress: &Progress, job: J) -> Vec<T>
+    where
+        T: Send,
+        J: Fn(&mut MonteCarlo<Box<dyn Sample>, ChaCha12Rng>) -> T + Sync,
+    {
+        parallel_map(chunks, self.threads, |index| {
+            let mut simulator = MonteCarlo::from_rng((self.make_sample)(), self.chunk_rng(index));
+            simulator.iterations = CHUNK_ITERATIONS.min(self.iterations - index * CHUNK_ITERATIONS);
+            let result = job(&mut simulator);
+            progress.advance(simulator.iterations);
+            result
+        })
+    }
+
+    /// Simulates the distribution of the statistic.
+    pub fn simulate_distribution(&self) -> Vec<f64> {
+        let progress = self.progress();
+        let statistics = self
+            .run(
+                0..self.chunks(),
+                &progress,
+                MonteCarlo::simulate_distribution,
+            )
+            .into_iter()
+            .flatten()
+            .collect();
+        progress.finish();
+        statistics
+    }
+
+    /// Compares the simulated statistics in the chunks with `statistic`.
+    fn count_in(&self, chunks: Range<usize>, progress: &Progress, statistic: f64) -> Counts {
+        self.run(chunks, progress, |simulator| {
+            Counts::new(&simulator.simulate_distribution(), statistic)
+        })
+        .into_iter()
+        .fold(Counts::default(), Counts::add)
+    }
+
+    /// Compares the simulated statistics with `statistic`.
+    pub fn count(&self, statistic: f64) -> Counts {
+        let progress = self.progress();
+        let counts = self.count_in(0..self.chunks(), &progress, statistic);
+        progress.finish();
+        counts
+    }
+
+    /// Compares the simulated statistics with `statistic`, stopping early as soon as
+    /// `is_done(counts)` returns `true`.
+    ///
+    /// The number of iterations of the simulation is the maximal number of iterations. The chunks
+    /// are simulated in batches that grow by a quarter of the iterations done so far, and
+    /// `is_done` is called after every batch. When the simulation stops, the number of iterations
+    /// is set to the number of iterations that were actually done.
+    ///
+    /// Returns the counts and whether `is_done` returned `true` before the maximal number of
+    /// iterations ran out. The progress is reported without a total, because the number of
+    /// iterations that will be done is unknown.
+    pub fn count_until<P>(&mut self, statistic: f64, mut is_done: P) -> (Counts, bool)
+    where
+        P: FnMut(&Counts) -> bool,
+    {
+        let chunks = self.chunks();
+        let progress = Progress::unbounded(self.quiet);
+        let mut counts = Counts::default();
+        let mut done = 0;
+        let mut reached = false;
+        while done < chunks {
+            let end = chunks.min(done + (done / 4).max(1));
+            counts = counts.add(self.count_in(done..end, &progress, statistic));
+            done = end;
+            if is_done(&counts) {
+                self.iterations = counts.iterations();
+                reached = true;
+                break;
+            }
+        }
+        progress.finish();
+        (counts, reached)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::distributions::{Distribution, Standard};
+    use rand::RngCore;
+
+    use super::*;
+
+    /// A sample whose statistic is a single uniform value.
+    struct UniformSample(f64);
+
+    impl Sample for UniformSample {
+        fn generate_sample(&mut self, rng: &mut dyn RngCore) {
+            self.0 = Standard.sample(rng);
+        }
+
+        fn evaluate(&self) -> f64 {
+            self.0
+        }
+    }
+
+    /// A number of iterations whose last chunk is shorter than the others.
+    const ITERATIONS: usize = 2 * CHUNK_ITERATIONS + CHUNK_ITERATIONS / 2;
+
+    /// The simulation of [`UniformSample`] on `threads` threads.
+    fn simulation(threads: usize) -> Simulation<impl Fn() -> Box<dyn Sample> + Sync> {
+        let run = RunArg {
+            seed: None,
+            threads: NonZeroUsize::new(threads).unwrap(),
+            quiet: true,
+        };
+        let make_sample = || Box::new(UniformSample(0.0)) as Box<dyn Sample>;
+        Simulation::new(make_sample, 42, Some(ITERATIONS), &run)
+    }
+
+    #[test]
+    fn distribution_doesnt_depend_on_threads() {
+        let statistics = simulation(1).simulate_distribution();
+        assert_eq!(statistics.len(), ITERATIONS);
+        assert_eq!(simulation(4).simulate_distribution(), statistics);
+    }
+
+    #[test]
+    fn counts_dont_depend_on_threads() {
+        let counts = simulation(1).count(0.5);
+        assert_eq!(counts.iterations(), ITERATIONS);
+        assert_eq!(simulation(4).count(0.5), counts);
+        let statistics = simulation(4).simulate_distribution();
+        assert_eq!(Counts::new(&statistics, 0.5), counts);
+    }
+
+    #[test]
+    fn chunks_are_in_order() {
+        let indices = parallel_map(3..20, NonZeroUsize::new(4).unwrap(), |index| index);
+        assert_eq!(indices, (3..20).collect::<Vec<_>>());
+    }
+}
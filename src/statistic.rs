@@ -0,0 +1,271 @@
+// Copyright 2024 Vladimir Kharchev
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Goodness-of-fit statistics of a dataset against a continuous distribution, and of two datasets
+//! against each other.
+//!
+//! Every function takes the datasets sorted in ascending order.
+
+use clap::ValueEnum;
+use statrs::distribution::ContinuousCDF;
+
+/// A goodness-of-fit statistic.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Statistic {
+    /// The Kolmogorov-Smirnov statistic `D`.
+    KolmogorovSmirnov,
+    /// The Anderson-Darling statistic `A²`.
+    AndersonDarling,
+    /// The Cramér–von Mises statistic `W²`.
+    CramerVonMises,
+    /// The Watson statistic `U²`.
+    Watson,
+}
+
+/// A statistic comparing two datasets.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TwoSampleStatistic {
+    /// The mean of the first group minus the mean of the second group.
+    MeanDifference,
+    /// The median of the first group minus the median of the second group.
+    MedianDifference,
+    /// The two-sample Kolmogorov-Smirnov distance.
+    KolmogorovSmirnov,
+}
+
+impl TwoSampleStatistic {
+    /// The name of the statistic as it is written on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            TwoSampleStatistic::MeanDifference => "mean-difference",
+            TwoSampleStatistic::MedianDifference => "median-difference",
+            TwoSampleStatistic::KolmogorovSmirnov => "kolmogorov-smirnov",
+        }
+    }
+
+    /// Calculates the statistic of the sorted datasets `a` and `b`.
+    pub fn evaluate(self, a: &[f64], b: &[f64]) -> f64 {
+        match self {
+            TwoSampleStatistic::MeanDifference => mean(a) - mean(b),
+            TwoSampleStatistic::MedianDifference => median(a) - median(b),
+            TwoSampleStatistic::KolmogorovSmirnov => kolmogorov_smirnov_two_sample(a, b),
+        }
+    }
+}
+
+impl Statistic {
+    /// Calculates the statistic of `sorted` against `distribution`.
+    pub fn evaluate<D: ContinuousCDF<f64, f64>>(self, sorted: &[f64], distribution: &D) -> f64 {
+        match self {
+            Statistic::KolmogorovSmirnov => kolmogorov_smirnov(sorted, distribution),
+            Statistic::AndersonDarling => anderson_darling(sorted, distribution),
+            Statistic::CramerVonMises => cramer_von_mises(sorted, distribution),
+            Statistic::Watson => watson(sorted, distribution),
+        }
+    }
+}
+
+/// Calculates the Kolmogorov-Smirnov statistic of `sorted` against `distribution`.
+#[allow(clippy::cast_precision_loss)]
+pub fn kolmogorov_smirnov<D: ContinuousCDF<f64, f64>>(sorted: &[f64], distribution: &D) -> f64 {
+    let n = sorted.len() as f64;
+    sorted
+        .iter()
+        .enumerate()
+        .map(|(i, &x)| {
+            let cdf = distribution.cdf(x);
+            f64::max(cdf - i as f64 / n, (i + 1) as f64 / n - cdf)
+        })
+        .fold(0.0, f64::max)
+}
+
+/// Calculates the asymptotic p-value of the Kolmogorov-Smirnov `statistic` of `n` values against
+/// a completely specified distribution.
+///
+/// The tail of the Kolmogorov distribution is evaluated at `(√n + 0.12 + 0.11 / √n) D`, which is
+/// Stephens' approximation for finite `n`.
+#[allow(clippy::cast_precision_loss)]
+pub fn kolmogorov_smirnov_pvalue(statistic: f64, n: usize) -> f64 {
+    let root = (n as f64).sqrt();
+    let lambda = (root + 0.12 + 0.11 / root) * statistic;
+    let mut sum = 0.0;
+    let mut sign = 1.0;
+    for k in 1..=100 {
+        let k = f64::from(k);
+        let term = sign * (-2.0 * k * k * lambda * lambda).exp();
+        sum += term;
+        if term.abs() <= 1e-10 * sum.abs() {
+            return (2.0 * sum).clamp(0.0, 1.0);
+        }
+        sign = -sign;
+    }
+    // The series doesn't converge for small statistics, whose p-value is close to 1
+    1.0
+}
+
+/// Calculates the Anderson-Darling statistic of `sorted` against `distribution`.
+///
+/// The statistic is infinite if a value lies outside the support of `distribution`.
+#[allow(clippy::cast_precision_loss)]
+pub fn anderson_darling<D: ContinuousCDF<f64, f64>>(sorted: &[f64], distribution: &D) -> f64 {
+    let n = sorted.len() as f64;
+    let sum: f64 = sorted
+        .iter()
+        .zip(sorted.iter().rev())
+        .enumerate()
+        .map(|(i, (&low, &high))| {
+            // The survival function is more precise than `1 - cdf` in the upper tail
+            (2 * i + 1) as f64 * (distribution.cdf(low).ln() + distribution.sf(high).ln())
+        })
+        .sum();
+    -n - sum / n
+}
+
+/// Calculates the Cramér–von Mises statistic of `sorted` against `distribution`.
+#[allow(clippy::cast_precision_loss)]
+pub fn cramer_von_mises<D: ContinuousCDF<f64, f64>>(sorted: &[f64], distribution: &D) -> f64 {
+    let n = sorted.len() as f64;
+    let sum: f64 = sorted
+        .iter()
+        .enumerate()
+        .map(|(i, &x)| (distribution.cdf(x) - (2 * i + 1) as f64 / (2.0 * n)).powi(2))
+        .sum();
+    1.0 / (12.0 * n) + sum
+}
+
+/// Calculates the Watson statistic of `sorted` against `distribution`.
+///
+/// It is the Cramér–von Mises statistic corrected for the mean of the probability integral
+/// transform, which makes it invariant under rotations of the circle.
+#[allow(clippy::cast_precision_loss)]
+pub fn watson<D: ContinuousCDF<f64, f64>>(sorted: &[f64], distribution: &D) -> f64 {
+    let n = sorted.len() as f64;
+    let mean = sorted.iter().map(|&x| distribution.cdf(x)).sum::<f64>() / n;
+    cramer_von_mises(sorted, distribution) - n * (mean - 0.5).powi(2)
+}
+
+/// Calculates the two-sample Kolmogorov-Smirnov statistic, the largest distance between the
+/// empirical distribution functions of `a` and `b`.
+///
+/// Tied values are passed together, so ties within and between the datasets are handled exactly.
+#[allow(clippy::cast_precision_loss)]
+pub fn kolmogorov_smirnov_two_sample(a: &[f64], b: &[f64]) -> f64 {
+    let (n, m) = (a.len() as f64, b.len() as f64);
+    let (mut i, mut j) = (0, 0);
+    let mut distance: f64 = 0.0;
+    while i < a.len() && j < b.len() {
+        let x = a[i].min(b[j]);
+        while i < a.len() && a[i] <= x {
+            i += 1;
+        }
+        while j < b.len() && b[j] <= x {
+            j += 1;
+        }
+        distance = distance.max((i as f64 / n - j as f64 / m).abs());
+    }
+    // Once a dataset is exhausted, the distance only shrinks
+    distance
+}
+
+/// Calculates the mean of `data`.
+#[allow(clippy::cast_precision_loss)]
+pub fn mean(data: &[f64]) -> f64 {
+    data.iter().sum::<f64>() / data.len() as f64
+}
+
+/// Calculates the median of `sorted`.
+fn median(sorted: &[f64]) -> f64 {
+    let middle = sorted.len() / 2;
+    if sorted.len() % 2 == 0 {
+        (sorted[middle - 1] + sorted[middle]) / 2.0
+    } else {
+        sorted[middle]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use statrs::distribution::Uniform;
+
+    use super::*;
+
+    /// A small dataset whose statistics against the standard uniform distribution are calculated
+    /// from the definitions.
+    const DATA: [f64; 3] = [0.1, 0.4, 0.7];
+
+    /// Asserts that `actual` is within `tolerance` of `expected`.
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{actual} is not within {tolerance} of {expected}"
+        );
+    }
+
+    /// The standard uniform distribution.
+    fn uniform() -> Uniform {
+        Uniform::new(0.0, 1.0).unwrap()
+    }
+
+    #[test]
+    fn kolmogorov_smirnov_of_small_dataset() {
+        // D+ = 1 - 0.7 and D- = 0.1
+        assert_close(kolmogorov_smirnov(&DATA, &uniform()), 0.3, 1e-12);
+    }
+
+    #[test]
+    fn anderson_darling_of_small_dataset() {
+        // A² = -n - Σ (2i - 1) (ln F(x_i) + ln(1 - F(x_{n+1-i}))) / n
+        let sum = (0.1f64 * 0.3).ln() + 3.0 * (0.4f64 * 0.6).ln() + 5.0 * (0.7f64 * 0.9).ln();
+        assert_close(anderson_darling(&DATA, &uniform()), -3.0 - sum / 3.0, 1e-12);
+    }
+
+    #[test]
+    fn anderson_darling_outside_support() {
+        assert!(anderson_darling(&[-0.5, 0.5], &uniform()).is_infinite());
+    }
+
+    #[test]
+    fn cramer_von_mises_of_small_dataset() {
+        // W² = 1/36 + (1/15)² + (1/10)² + (2/15)²
+        assert_close(cramer_von_mises(&DATA, &uniform()), 0.06, 1e-12);
+    }
+
+    #[test]
+    fn watson_of_small_dataset() {
+        // U² = W² - n (mean - 1/2)² with the mean 0.4 of the values of the distribution function
+        assert_close(watson(&DATA, &uniform()), 0.03, 1e-12);
+    }
+
+    #[test]
+    fn kolmogorov_smirnov_pvalue_at_critical_value() {
+        // The critical value of the Kolmogorov distribution at the 5% level is 1.3581
+        assert_close(kolmogorov_smirnov_pvalue(1.3581e-3, 1_000_000), 0.05, 1e-3);
+        assert_close(kolmogorov_smirnov_pvalue(0.0, 10), 1.0, 1e-12);
+    }
+
+    #[test]
+    fn two_sample_kolmogorov_smirnov() {
+        assert_close(
+            kolmogorov_smirnov_two_sample(&[1.0, 2.0, 3.0], &[4.0, 5.0]),
+            1.0,
+            1e-12,
+        );
+        // The tied value 2 is passed by both datasets at once
+        assert_close(
+            kolmogorov_smirnov_two_sample(&[1.0, 2.0], &[2.0, 3.0]),
+            0.5,
+            1e-12,
+        );
+    }
+}
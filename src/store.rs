@@ -0,0 +1,288 @@
+// Copyright 2024 Vladimir Kharchev
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Saving simulated distributions to files and loading them back.
+//!
+//! A file of version [`VERSION`] consists of
+//! - the magic bytes [`MAGIC`],
+//! - the version as a little-endian `u16`,
+//! - the length of the metadata as a little-endian `u64`, followed by the metadata of the
+//!   simulation as a JSON object in UTF-8,
+//! - the number of statistics as a little-endian `u64`, followed by the statistics as
+//!   little-endian `f64` in the order in which they were simulated.
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+use serde_json::{Map, Value};
+
+use crate::distribution::Family;
+use crate::fitting::FitFamily;
+use crate::gof::Test;
+use crate::output::Metadata;
+
+/// The bytes at the start of every file.
+pub const MAGIC: &[u8; 6] = b"MCDIST";
+
+/// The version of the format written by [`save`].
+pub const VERSION: u16 = 1;
+
+/// An error that occurred while saving or loading a distribution.
+#[derive(Debug)]
+pub enum StoreError {
+    /// The file couldn't be read or written.
+    Io(PathBuf, io::Error),
+    /// The file is not a saved distribution.
+    Format(PathBuf, String),
+    /// The file was written by a version of the format that is not supported.
+    Version(PathBuf, u16),
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::Io(path, err) => write!(f, "can't access {}: {err}", path.display()),
+            StoreError::Format(path, err) => {
+                write!(f, "{} is not a saved distribution: {err}", path.display())
+            }
+            StoreError::Version(path, version) => write!(
+                f,
+                "{} has version {version} of the format, but only version {VERSION} is supported",
+                path.display()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+/// A distribution loaded from a file.
+pub struct Saved {
+    /// The description of the simulation.
+    pub metadata: Metadata,
+    /// The simulated statistics.
+    pub statistics: Vec<f64>,
+}
+
+/// Saves the `statistics` simulated as described by `metadata` to the file at `path`.
+pub fn save(path: &Path, metadata: &Metadata, statistics: &[f64]) -> Result<(), StoreError> {
+    let io_error = |err: io::Error| StoreError::Io(path.to_owned(), err);
+    let header = Value::Object(metadata.to_json()).to_string();
+    let mut out = BufWriter::new(File::create(path).map_err(io_error)?);
+    out.write_all(MAGIC).map_err(io_error)?;
+    out.write_all(&VERSION.to_le_bytes()).map_err(io_error)?;
+    out.write_all(&(header.len() as u64).to_le_bytes())
+        .map_err(io_error)?;
+    out.write_all(header.as_bytes()).map_err(io_error)?;
+    out.write_all(&(statistics.len() as u64).to_le_bytes())
+        .map_err(io_error)?;
+    for x in statistics {
+        out.write_all(&x.to_le_bytes()).map_err(io_error)?;
+    }
+    out.flush().map_err(io_error)
+}
+
+/// Loads a distribution saved by [`save`] from the file at `path`.
+pub fn load(path: &Path) -> Result<Saved, StoreError> {
+    let format_error = |err: &str| StoreError::Format(path.to_owned(), err.to_owned());
+    let read_error = |err: io::Error| match err.kind() {
+        io::ErrorKind::UnexpectedEof => format_error("the file is truncated"),
+        _ => StoreError::Io(path.to_owned(), err),
+    };
+    let mut input = BufReader::new(File::open(path).map_err(read_error)?);
+    let mut magic = [0; MAGIC.len()];
+    input.read_exact(&mut magic).map_err(read_error)?;
+    if &magic != MAGIC {
+        return Err(format_error("the magic bytes don't match"));
+    }
+    let mut version = [0; 2];
+    input.read_exact(&mut version).map_err(read_error)?;
+    let version = u16::from_le_bytes(version);
+    if version != VERSION {
+        return Err(StoreError::Version(path.to_owned(), version));
+    }
+    let mut word = [0; 8];
+    input.read_exact(&mut word).map_err(read_error)?;
+    let length = usize::try_from(u64::from_le_bytes(word))
+        .map_err(|_| format_error("the metadata is too long"))?;
+    let mut header = Vec::new();
+    input
+        .by_ref()
+        .take(length as u64)
+        .read_to_end(&mut header)
+        .map_err(read_error)?;
+    if header.len() != length {
+        return Err(format_error("the file is truncated"));
+    }
+    let metadata = match serde_json::from_slice(&header) {
+        Ok(Value::Object(object)) => metadata_from_json(&object),
+        _ => None,
+    }
+    .ok_or_else(|| format_error("the metadata is invalid"))?;
+    input.read_exact(&mut word).map_err(read_error)?;
+    let count = u64::from_le_bytes(word);
+    if count == 0 {
+        return Err(format_error("the file contains no statistics"));
+    }
+    if usize::try_from(count).ok() != Some(metadata.iterations) {
+        return Err(format_error(
+            "the number of statistics differs from the number of iterations",
+        ));
+    }
+    let mut statistics = Vec::new();
+    for _ in 0..count {
+        input.read_exact(&mut word).map_err(read_error)?;
+        statistics.push(f64::from_le_bytes(word));
+    }
+    Ok(Saved {
+        metadata,
+        statistics,
+    })
+}
+
+/// Reconstructs the metadata from the JSON object written by [`Metadata::to_json`].
+///
+/// Returns `None` if a field is missing or names an unknown test or family.
+fn metadata_from_json(object: &Map<String, Value>) -> Option<Metadata> {
+    let name = |field: &str| object.get(field)?.as_str();
+    let count = |field: &str| usize::try_from(object.get(field)?.as_u64()?).ok();
+    let distribution = object.get("distribution")?.as_object()?;
+    let family = Family::from_str(distribution.get("family")?.as_str()?, false).ok()?;
+    let parameters = family
+        .parameter_names()
+        .filter_map(|parameter| Some((parameter, distribution.get(parameter)?.as_f64()?)))
+        .collect();
+    let fit = match object.get("fit")? {
+        Value::Null => None,
+        fit => Some(FitFamily::from_str(fit.as_str()?, false).ok()?.name()),
+    };
+    Some(Metadata {
+        test: Test::from_str(name("test")?, false).ok()?.name(),
+        samples: count("samples")?,
+        iterations: count("iterations")?,
+        distribution: family.name(),
+        parameters,
+        fit,
+        bootstrap: object.get("bootstrap")?.as_bool()?,
+        seed: object.get("seed")?.as_u64()?,
+        data: name("data").map(PathBuf::from),
+        target: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    /// The metadata of a small simulation.
+    fn metadata(iterations: usize) -> Metadata {
+        Metadata {
+            test: "kolmogorov-smirnov",
+            samples: 10,
+            iterations,
+            distribution: "normal",
+            parameters: vec![("mean", 0.5), ("std-dev", 2.0)],
+            fit: None,
+            bootstrap: false,
+            seed: 7,
+            data: None,
+            target: None,
+        }
+    }
+
+    /// A path in the temporary directory that is unique to the test `name`.
+    fn temporary(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("monty_carlos_store_{}_{name}", std::process::id()))
+    }
+
+    /// Writes `bytes` to a temporary file and loads it.
+    fn load_bytes(name: &str, bytes: &[u8]) -> Result<Saved, StoreError> {
+        let path = temporary(name);
+        fs::write(&path, bytes).unwrap();
+        let saved = load(&path);
+        fs::remove_file(&path).unwrap();
+        saved
+    }
+
+    /// The bytes of a file saved with `statistics`.
+    fn saved_bytes(name: &str, metadata: &Metadata, statistics: &[f64]) -> Vec<u8> {
+        let path = temporary(name);
+        save(&path, metadata, statistics).unwrap();
+        let bytes = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn round_trip() {
+        let statistics = [0.25, 0.125, f64::MIN_POSITIVE, 3.5];
+        let bytes = saved_bytes("round_trip", &metadata(statistics.len()), &statistics);
+        let saved = load_bytes("round_trip", &bytes).unwrap();
+        assert_eq!(saved.statistics, statistics);
+        assert_eq!(
+            saved.metadata.to_json(),
+            metadata(statistics.len()).to_json()
+        );
+    }
+
+    #[test]
+    fn rejects_bad_magic() {
+        let mut bytes = saved_bytes("bad_magic", &metadata(1), &[0.5]);
+        bytes[0] = b'X';
+        assert!(matches!(
+            load_bytes("bad_magic", &bytes),
+            Err(StoreError::Format(..))
+        ));
+    }
+
+    #[test]
+    fn rejects_other_version() {
+        let mut bytes = saved_bytes("other_version", &metadata(1), &[0.5]);
+        bytes[MAGIC.len()..MAGIC.len() + 2].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        assert!(matches!(
+            load_bytes("other_version", &bytes),
+            Err(StoreError::Version(_, version)) if version == VERSION + 1
+        ));
+    }
+
+    #[test]
+    fn rejects_truncated_file() {
+        let bytes = saved_bytes("truncated", &metadata(2), &[0.5, 0.25]);
+        for length in [3, MAGIC.len() + 5, bytes.len() - 4] {
+            assert!(matches!(
+                load_bytes("truncated", &bytes[..length]),
+                Err(StoreError::Format(..))
+            ));
+        }
+    }
+
+    #[test]
+    fn rejects_wrong_count() {
+        let bytes = saved_bytes("no_statistics", &metadata(0), &[]);
+        assert!(matches!(
+            load_bytes("no_statistics", &bytes),
+            Err(StoreError::Format(..))
+        ));
+        let bytes = saved_bytes("wrong_count", &metadata(3), &[0.5, 0.25]);
+        assert!(matches!(
+            load_bytes("wrong_count", &bytes),
+            Err(StoreError::Format(..))
+        ));
+    }
+}
@@ -0,0 +1,236 @@
+// Copyright 2024 Vladimir Kharchev
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The sweep mode of the `power` subcommand, which simulates the power curve over the values of a
+//! parameter of the alternative distribution.
+
+use std::io::{self, Write};
+
+use serde_json::{json, Map, Value};
+
+use crate::distribution::DistributionArg;
+use crate::error::CliError;
+use crate::output::{self, Format};
+use crate::power::{Power, Study};
+
+/// The range of values of a parameter of the alternative distribution.
+#[derive(Clone, Debug)]
+pub struct Sweep {
+    /// The name of the parameter, like its command-line option without the leading dashes.
+    pub parameter: String,
+    /// The values of the parameter in increasing order.
+    pub values: Vec<f64>,
+}
+
+impl Sweep {
+    /// Sets the parameter in `alternative` to `value`.
+    pub fn apply(&self, alternative: &mut DistributionArg, value: f64) -> Result<(), CliError> {
+        alternative
+            .set(&self.parameter, value)
+            .map_err(CliError::Parameter)
+    }
+}
+
+/// A point of the power curve.
+struct Point {
+    /// The value of the swept parameter.
+    value: f64,
+    /// The alternative distribution with the value of the parameter.
+    alternative: DistributionArg,
+    /// The power against the alternative.
+    power: Power,
+}
+
+/// The number of decimal places of a number as it is written.
+///
+/// Returns `None` if the number is written with an exponent.
+fn decimals(number: &str) -> Option<usize> {
+    if number.contains(['e', 'E']) {
+        return None;
+    }
+    Some(
+        number
+            .split_once('.')
+            .map_or(0, |(_, fraction)| fraction.len()),
+    )
+}
+
+/// Parses a sweep written as `PARAMETER=FROM:TO:STEP`.
+///
+/// Unless a number is written with an exponent, the values are rounded to the largest number of
+/// decimal places of the bounds and the step, so that `0:1:0.1` produces `0.3` rather than
+/// `0.30000000000000004`.
+pub fn parse_sweep(value: &str) -> Result<Sweep, String> {
+    let (parameter, range) = value
+        .split_once('=')
+        .ok_or("expected PARAMETER=FROM:TO:STEP")?;
+    let bounds: Vec<&str> = range.split(':').collect();
+    let [from, to, step] = bounds[..] else {
+        return Err(format!("expected FROM:TO:STEP, got `{range}`"));
+    };
+    let number = |number: &str| {
+        number
+            .parse::<f64>()
+            .map_err(|err| format!("invalid number `{number}`: {err}"))
+    };
+    let (first, last, increment) = (number(from)?, number(to)?, number(step)?);
+    let is_finite = [first, last, increment].iter().all(|x| x.is_finite());
+    if !is_finite || first > last || increment <= 0.0 {
+        return Err("the range must be finite with FROM at most TO and a positive STEP".to_owned());
+    }
+    let places = [from, to, step]
+        .into_iter()
+        .try_fold(0, |places, number| Some(places.max(decimals(number)?)));
+    let round = |value: f64| match places {
+        Some(places) => {
+            #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+            let scale = 10f64.powi(places.min(15) as i32);
+            (value * scale).round() / scale
+        }
+        None => value,
+    };
+    // A small tolerance keeps the last value that is only missed by rounding
+    let count = ((last - first) / increment + 1e-9).floor();
+    if count >= 1e6 {
+        return Err("the range has too many values".to_owned());
+    }
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let count = count as usize;
+    #[allow(clippy::cast_precision_loss)]
+    let values = (0..=count)
+        .map(|index| round(first + index as f64 * increment))
+        .collect();
+    Ok(Sweep {
+        parameter: parameter.to_owned(),
+        values,
+    })
+}
+
+/// Simulates the power of `study` for datasets of size `samples` against every alternative of
+/// the `sweep` and prints the power curve in `format`.
+///
+/// The critical value is simulated once, because it doesn't depend on the alternative.
+pub fn run(study: &Study, sweep: &Sweep, samples: usize, format: Format) -> Result<(), CliError> {
+    let (critical_value, iterations) = study.critical_value(samples)?;
+    let mut curve = Vec::with_capacity(sweep.values.len());
+    for &value in &sweep.values {
+        let mut alternative = study.args.alternative;
+        sweep.apply(&mut alternative, value)?;
+        let distribution = alternative.build().map_err(CliError::Parameter)?;
+        curve.push(Point {
+            value,
+            alternative,
+            power: study.power_against(distribution, samples, critical_value, iterations)?,
+        });
+    }
+    Ok(print(study, sweep, &curve, format)?)
+}
+
+/// Prints the power `curve` of the `sweep` of `study` to the standard output.
+fn print(study: &Study, sweep: &Sweep, curve: &[Point], format: Format) -> io::Result<()> {
+    let mut out = io::stdout().lock();
+    let parameter = &sweep.parameter;
+    match format {
+        Format::Text => {
+            for point in curve {
+                let estimate = &point.power.power;
+                writeln!(
+                    out,
+                    "{parameter} = {}: power = {} [{}, {}]",
+                    point.value, estimate.probability, estimate.lower, estimate.upper
+                )?;
+            }
+            Ok(())
+        }
+        Format::Json => {
+            let mut object = Map::new();
+            study.insert_json(&mut object);
+            object.insert("parameter".to_owned(), json!(parameter));
+            let curve = curve
+                .iter()
+                .map(|point| {
+                    let alternative = &point.alternative;
+                    let mut object = Map::new();
+                    object.insert(parameter.clone(), json!(point.value));
+                    object.insert(
+                        "alternative".to_owned(),
+                        output::distribution_json(
+                            alternative.family().name(),
+                            &alternative.parameters(),
+                        ),
+                    );
+                    object.extend(point.power.to_json());
+                    Value::Object(object)
+                })
+                .collect();
+            object.insert("curve".to_owned(), Value::Array(curve));
+            serde_json::to_writer_pretty(&mut out, &Value::Object(object))?;
+            writeln!(out)
+        }
+        Format::Csv | Format::Tsv => {
+            let separator = format.separator().unwrap_or_default();
+            writeln!(
+                out,
+                "{parameter}{separator}{}",
+                Power::HEADER.join(separator)
+            )?;
+            for point in curve {
+                let row = point.power.row().join(separator);
+                writeln!(out, "{}{separator}{row}", point.value)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The rounding has to produce the values exactly
+    #[allow(clippy::float_cmp)]
+    #[test]
+    fn values_are_rounded_to_the_decimal_places() {
+        let sweep = parse_sweep("mean=0:1:0.1").unwrap();
+        assert_eq!(sweep.parameter, "mean");
+        assert_eq!(sweep.values.len(), 11);
+        assert_eq!(sweep.values[3], 0.3);
+        assert_eq!(sweep.values[10], 1.0);
+    }
+
+    #[allow(clippy::float_cmp)]
+    #[test]
+    fn last_value_is_kept_if_only_missed_by_rounding() {
+        // 0.3 / 0.1 is slightly less than 3
+        let sweep = parse_sweep("shape=0:0.3:0.1").unwrap();
+        assert_eq!(sweep.values.len(), 4);
+        assert_eq!(sweep.values[3], 0.3);
+        // A value beyond TO is not produced
+        assert_eq!(
+            parse_sweep("rate=1:2:0.3").unwrap().values,
+            [1.0, 1.3, 1.6, 1.9]
+        );
+    }
+
+    #[test]
+    fn invalid_ranges_are_rejected() {
+        assert!(parse_sweep("mean=1:0:0.1").is_err());
+        assert!(parse_sweep("mean=0:1:0").is_err());
+        assert!(parse_sweep("mean=0:1:-0.1").is_err());
+        assert!(parse_sweep("mean=0:inf:1").is_err());
+        assert!(parse_sweep("mean=0:1").is_err());
+        assert!(parse_sweep("0:1:0.1").is_err());
+        assert!(parse_sweep("mean=0:1e7:1").is_err());
+    }
+}
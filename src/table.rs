@@ -0,0 +1,140 @@
+// Copyright 2024 Vladimir Kharchev
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The `table` subcommand, which tabulates critical values of a test.
+
+use std::io::{self, Write};
+
+use clap::{Args, ValueEnum};
+
+use crate::empirical;
+use crate::error::CliError;
+use crate::estimate;
+use crate::gof::{self, ModelArg, Test};
+use crate::simulation::{RunArg, Simulation};
+
+/// Enum for the CLI option to choose the format of the table.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TableFormat {
+    /// Comma-separated values with a header row.
+    Csv,
+    /// Tab-separated values with a header row.
+    Tsv,
+    /// A Markdown table.
+    Markdown,
+}
+
+/// The command-line arguments of the `table` subcommand.
+#[derive(Args, Debug)]
+pub struct TableArg {
+    /// The sample sizes of the rows of the table.
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_values_t = [5, 10, 15, 20, 25, 30, 40, 50, 100, 200, 500]
+    )]
+    samples: Vec<usize>,
+    /// The significance levels of the columns of the table.
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_values_t = [0.1, 0.05, 0.01],
+        value_parser = |value: &str| estimate::parse_probability(value, "the significance level")
+    )]
+    alpha: Vec<f64>,
+    /// Number of iterations of the simulation for every sample size.
+    #[arg(long)]
+    iterations: Option<usize>,
+    /// The seed and the number of threads of the simulations.
+    #[command(flatten)]
+    run: RunArg,
+    /// The format of the table.
+    #[arg(long, value_enum, default_value_t = TableFormat::Csv)]
+    format: TableFormat,
+    /// The null hypothesis of the test.
+    #[command(flatten, next_help_heading = "Null distribution")]
+    model: ModelArg,
+    /// The statistical test whose critical values are tabulated.
+    #[arg(value_enum)]
+    test: Test,
+}
+
+/// Prints the table of critical values described by `args`.
+///
+/// The critical value at the significance level `alpha` is the empirical quantile of order
+/// `1 - alpha` of the simulated statistics. Every sample size is simulated with the same seed.
+pub fn run(args: &TableArg) -> Result<(), CliError> {
+    let setup = args.model.setup(args.test)?;
+    let seed = args.run.seed();
+    let simulators = args
+        .samples
+        .iter()
+        .map(|&samples| {
+            let simulator =
+                Simulation::new(setup.sampler(samples)?, seed, args.iterations, &args.run);
+            gof::check_iterations(simulator.iterations)?;
+            Ok((samples, simulator))
+        })
+        .collect::<Result<Vec<_>, CliError>>()?;
+    let rows = simulators.into_iter().map(|(samples, simulator)| {
+        let mut distribution = simulator.simulate_distribution();
+        distribution.sort_unstable_by(f64::total_cmp);
+        let critical_values: Vec<f64> = args
+            .alpha
+            .iter()
+            .map(|alpha| empirical::quantile(&distribution, 1.0 - alpha))
+            .collect();
+        (samples, critical_values)
+    });
+    Ok(print(args.format, &args.alpha, rows)?)
+}
+
+/// Prints the rows of the table as soon as they are simulated.
+fn print(
+    format: TableFormat,
+    alpha: &[f64],
+    rows: impl Iterator<Item = (usize, Vec<f64>)>,
+) -> io::Result<()> {
+    let mut out = io::stdout().lock();
+    let separator = match format {
+        TableFormat::Csv => ",",
+        TableFormat::Tsv => "\t",
+        TableFormat::Markdown => " | ",
+    };
+    let join = |first: String, rest: Vec<String>| {
+        let line = std::iter::once(first).chain(rest).collect::<Vec<_>>();
+        match format {
+            TableFormat::Markdown => format!("| {} |", line.join(separator)),
+            TableFormat::Csv | TableFormat::Tsv => line.join(separator),
+        }
+    };
+    let header: Vec<String> = alpha
+        .iter()
+        .map(|alpha| match format {
+            TableFormat::Markdown => format!("α = {alpha}"),
+            TableFormat::Csv | TableFormat::Tsv => alpha.to_string(),
+        })
+        .collect();
+    writeln!(out, "{}", join("samples".to_owned(), header))?;
+    if format == TableFormat::Markdown {
+        let rule = alpha.iter().map(|_| "---:".to_owned()).collect();
+        writeln!(out, "{}", join("---:".to_owned(), rule))?;
+    }
+    for (samples, critical_values) in rows {
+        let values = critical_values.iter().map(f64::to_string).collect();
+        writeln!(out, "{}", join(samples.to_string(), values))?;
+        out.flush()?;
+    }
+    Ok(())
+}
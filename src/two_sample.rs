@@ -0,0 +1,173 @@
+// Copyright 2024 Vladimir Kharchev
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The `two-sample` subcommand, which simulates the two-sample Kolmogorov-Smirnov test.
+
+use std::path::{Path, PathBuf};
+
+use clap::{Args, ValueEnum};
+use monty_carlos::sample::Sample;
+use serde_json::{json, Map};
+
+use crate::data;
+use crate::error::CliError;
+use crate::estimate::{EstimateArg, Tail};
+use crate::gof;
+use crate::output::{Format, PValueReport};
+use crate::sample::{PermutationSample, TwoSampleKSSample};
+use crate::simulation::{RunArg, Simulation};
+use crate::statistic::{self, TwoSampleStatistic};
+
+/// Enum for the CLI option to choose how the null distribution of the statistic is simulated.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Resampling {
+    /// Shuffle the pooled values of the datasets, which is exact for any distribution.
+    Permutation,
+    /// Draw new datasets of the same sizes from a continuous distribution.
+    MonteCarlo,
+}
+
+impl Resampling {
+    /// The name of the method as it is written on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            Resampling::Permutation => "permutation",
+            Resampling::MonteCarlo => "monte-carlo",
+        }
+    }
+}
+
+/// The command-line arguments of the `two-sample` subcommand.
+///
+/// The test either compares two datasets read from files, or tests a given statistic for the
+/// datasets of the given sizes.
+#[derive(Args, Debug)]
+pub struct TwoSampleArg {
+    /// The size of the first simulated dataset.
+    #[arg(
+        long,
+        required_unless_present = "data_a",
+        conflicts_with = "data_a",
+        requires = "test_statistic"
+    )]
+    samples_a: Option<usize>,
+    /// The size of the second simulated dataset.
+    #[arg(
+        long,
+        required_unless_present = "data_b",
+        conflicts_with = "data_b",
+        requires = "test_statistic"
+    )]
+    samples_b: Option<usize>,
+    /// Calculate the probability that the statistic is at least the given value.
+    #[arg(long, conflicts_with = "data_a")]
+    test_statistic: Option<f64>,
+    /// The file of the first dataset (`-` for the standard input).
+    #[arg(long, value_name = "PATH", requires = "data_b")]
+    data_a: Option<PathBuf>,
+    /// The file of the second dataset (`-` for the standard input).
+    #[arg(long, value_name = "PATH", requires = "data_a")]
+    data_b: Option<PathBuf>,
+    /// How the null distribution is simulated for the datasets in files.
+    ///
+    /// The default is the permutation test. Without files, new datasets are always drawn.
+    #[arg(long, value_enum, requires = "data_a")]
+    method: Option<Resampling>,
+    /// Number of iterations of the simulation.
+    #[arg(long)]
+    iterations: Option<usize>,
+    /// The seed and the number of threads of the simulation.
+    #[command(flatten)]
+    run: RunArg,
+    /// How the p-value is estimated.
+    #[command(flatten)]
+    estimate: EstimateArg,
+    /// The format of the output.
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+}
+
+/// Reads and sorts the dataset in the file.
+fn read_sorted(path: &Path) -> Result<Vec<f64>, CliError> {
+    let mut data = data::read(path)?;
+    data.sort_unstable_by(f64::total_cmp);
+    Ok(data)
+}
+
+/// Runs the two-sample test described by `args` and prints the p-value.
+///
+/// The p-value is the upper tail of the statistic, because larger distances are evidence against
+/// the null hypothesis.
+pub fn run(args: &TwoSampleArg) -> Result<(), CliError> {
+    let datasets = match (&args.data_a, &args.data_b) {
+        (Some(a), Some(b)) => Some((read_sorted(a)?, read_sorted(b)?)),
+        _ => None,
+    };
+    let (samples_a, samples_b, statistic) = match &datasets {
+        Some((a, b)) => (
+            a.len(),
+            b.len(),
+            statistic::kolmogorov_smirnov_two_sample(a, b),
+        ),
+        None => (
+            args.samples_a.expect("the sizes are required without data"),
+            args.samples_b.expect("the sizes are required without data"),
+            args.test_statistic
+                .expect("the statistic is required without data"),
+        ),
+    };
+    if samples_a == 0 || samples_b == 0 {
+        return Err(CliError::Parameter(
+            "the sizes of both datasets must be positive".to_owned(),
+        ));
+    }
+    let method = match datasets {
+        Some(_) => args.method.unwrap_or(Resampling::Permutation),
+        None => Resampling::MonteCarlo,
+    };
+    let make_sample = || -> Box<dyn Sample> {
+        match (&datasets, method) {
+            (Some((a, b)), Resampling::Permutation) => Box::new(
+                PermutationSample::new(a, b, TwoSampleStatistic::KolmogorovSmirnov)
+                    .expect("the datasets are not empty"),
+            ),
+            _ => Box::new(
+                TwoSampleKSSample::new(samples_a, samples_b).expect("the sizes are positive"),
+            ),
+        }
+    };
+    let seed = args.run.seed();
+    let simulator = Simulation::new(make_sample, seed, args.iterations, &args.run);
+    gof::check_iterations(simulator.iterations)?;
+    let counts = simulator.count(statistic);
+    let path = |path: &Option<PathBuf>| json!(path.as_ref().map(|path| path.display().to_string()));
+    let mut description = Map::new();
+    description.insert("test".to_owned(), json!("two-sample-kolmogorov-smirnov"));
+    description.insert("samples_a".to_owned(), json!(samples_a));
+    description.insert("samples_b".to_owned(), json!(samples_b));
+    description.insert("method".to_owned(), json!(method.name()));
+    description.insert("iterations".to_owned(), json!(simulator.iterations));
+    description.insert("seed".to_owned(), json!(seed));
+    description.insert("data_a".to_owned(), path(&args.data_a));
+    description.insert("data_b".to_owned(), path(&args.data_b));
+    let report = PValueReport {
+        description,
+        iterations: simulator.iterations,
+        statistic,
+        show_statistic: args.data_a.is_some(),
+        tail: Tail::Upper,
+        pvalue: args.estimate.estimate(Tail::Upper, &counts),
+    };
+    Ok(report.print(args.format)?)
+}